@@ -0,0 +1,215 @@
+use super::Options;
+use crate::Error;
+use prometheus_client::{
+    encoding::text::{Encode, EncodeMetric, Encoder},
+    metrics::{counter::Counter, MetricType},
+};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::sync::atomic::AtomicBool;
+use std::{fmt, io};
+
+/// A metric whose labeled samples are produced at scrape time by a
+/// callback, instead of being stored in a [`Family`](super::Family).
+///
+/// The callback returns pairs of label sets, encoded with [`Serialize`], and
+/// values, such as [`f64`] or [`u64`].
+///
+/// Like the series of a family, the label sets are serialized into a buffer
+/// before being written, and the options of a family, such as constant
+/// labels, apply to them.
+///
+/// #### Examples
+///
+/// ```rust
+/// # use prometheus_client::{encoding::text::encode, registry::Registry};
+/// # use serde::Serialize;
+/// # use serde_prometheus_labels::Collector;
+/// #
+/// #[derive(Serialize)]
+/// struct Labels {
+///     tenant: &'static str,
+/// }
+///
+/// let queues = [("unionize.org", 3u64), ("amazon.com", 0)];
+/// let mut registry = Registry::default();
+///
+/// registry.register(
+///     "queue_depth",
+///     "Number of queued jobs per tenant",
+///     Collector::gauge(move || {
+///         queues
+///             .into_iter()
+///             .map(|(tenant, depth)| (Labels { tenant }, depth))
+///     }),
+/// );
+///
+/// let mut serialized = vec![];
+/// encode(&mut serialized, &registry).unwrap();
+///
+/// assert_eq!(
+///     String::from_utf8(serialized).unwrap(),
+///     concat!(
+///         "# HELP queue_depth Number of queued jobs per tenant.\n",
+///         "# TYPE queue_depth gauge\n",
+///         "queue_depth{tenant=\"unionize.org\"} 3\n",
+///         "queue_depth{tenant=\"amazon.com\"} 0\n",
+///         "# EOF\n",
+///     ),
+/// );
+/// ```
+pub struct Collector<F, S> {
+    collect: F,
+    metric_type: MetricType,
+    options: Options<S>,
+}
+
+impl<F, I, S, V> Collector<F, S>
+where
+    F: Fn() -> I,
+    I: IntoIterator<Item = (S, V)>,
+{
+    /// Creates a collector whose samples are gauges.
+    pub fn gauge(collect: F) -> Self {
+        Self {
+            collect,
+            metric_type: MetricType::Gauge,
+            options: Default::default(),
+        }
+    }
+
+    /// Creates a collector whose samples are counters.
+    pub fn counter(collect: F) -> Self {
+        Self {
+            collect,
+            metric_type: MetricType::Counter,
+            options: Default::default(),
+        }
+    }
+}
+
+impl<F, S> Collector<F, S> {
+    /// See [`Family::with_const_labels`](super::Family::with_const_labels).
+    pub fn with_const_labels(mut self, const_labels: &impl Serialize) -> Result<Self, Error> {
+        self.options.set_const_labels(const_labels)?;
+
+        Ok(self)
+    }
+
+    /// See [`Family::with_relabeler`](super::Family::with_relabeler).
+    #[cfg(feature = "relabel")]
+    pub fn with_relabeler(mut self, relabeler: crate::relabel::Relabeler) -> Self {
+        self.options.set_relabeler(relabeler);
+
+        self
+    }
+
+    /// See
+    /// [`Family::skip_invalid_series`](super::Family::skip_invalid_series).
+    ///
+    /// As the label sets of a collector aren't stored, invalid series are
+    /// reported to `on_error` each time they are skipped.
+    pub fn skip_invalid_series(
+        mut self,
+        errors: Counter,
+        on_error: impl Fn(&S, &Error) + Send + Sync + 'static,
+    ) -> Self {
+        self.options.skip_invalid_series(errors, on_error);
+
+        self
+    }
+
+    /// See [`Family::sort_by_labels`](super::Family::sort_by_labels).
+    pub fn sort_by_labels(mut self) -> Self {
+        self.options.sort_by_labels();
+
+        self
+    }
+
+    /// See [`Family::sort_by`](super::Family::sort_by).
+    pub fn sort_by(mut self, compare: impl Fn(&S, &S) -> Ordering + Send + Sync + 'static) -> Self {
+        self.options.sort_by(compare);
+
+        self
+    }
+}
+
+impl<F, I, S, V> EncodeMetric for Collector<F, S>
+where
+    F: Fn() -> I,
+    I: IntoIterator<Item = (S, V)>,
+    S: Serialize,
+    V: Encode,
+{
+    fn encode(&self, mut encoder: Encoder) -> io::Result<()> {
+        let samples = (self.collect)()
+            .into_iter()
+            .map(|(label_set, value)| {
+                let sample = Sample {
+                    value,
+                    metric_type: self.metric_type,
+                };
+
+                (label_set, sample, AtomicBool::new(false))
+            })
+            .collect::<Vec<_>>();
+
+        self.options.encode_buffered(
+            &mut encoder,
+            samples
+                .iter()
+                .map(|(label_set, sample, reported)| (label_set, sample, reported)),
+        )
+    }
+
+    fn metric_type(&self) -> MetricType {
+        self.metric_type
+    }
+}
+
+impl<F, S> fmt::Debug for Collector<F, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Collector")
+            .field("metric_type", &self.metric_type)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A sample returned by the callback of a [`Collector`].
+struct Sample<V> {
+    value: V,
+    metric_type: MetricType,
+}
+
+impl<V> EncodeMetric for Sample<V>
+where
+    V: Encode,
+{
+    fn encode(&self, mut encoder: Encoder) -> io::Result<()> {
+        let mut bucket_encoder = match self.metric_type {
+            MetricType::Counter => encoder.encode_suffix("total")?,
+            _ => encoder.no_suffix()?,
+        };
+
+        bucket_encoder
+            .no_bucket()?
+            .encode_value(Value(&self.value))?
+            .no_exemplar()
+    }
+
+    fn metric_type(&self) -> MetricType {
+        self.metric_type
+    }
+}
+
+/// Encodes a borrowed value.
+struct Value<'a, V>(&'a V);
+
+impl<V> Encode for Value<'_, V>
+where
+    V: Encode,
+{
+    fn encode(&self, writer: &mut dyn io::Write) -> Result<(), std::io::Error> {
+        self.0.encode(writer)
+    }
+}
@@ -0,0 +1,282 @@
+use super::Bridge;
+use crate::Error;
+use prometheus_client::{
+    encoding::text::{Encode, EncodeMetric, Encoder},
+    metrics::{
+        counter::{self, Counter},
+        exemplar,
+        histogram::Histogram,
+        MetricType, TypedMetric,
+    },
+};
+use serde::Serialize;
+#[cfg(any(target_arch = "mips", target_arch = "powerpc"))]
+use std::sync::atomic::AtomicU32;
+#[cfg(not(any(target_arch = "mips", target_arch = "powerpc")))]
+use std::sync::atomic::AtomicU64;
+use std::{error, fmt, io};
+
+/// The maximum number of chars in the names and values of the labels of an
+/// exemplar, as specified by OpenMetrics.
+const MAX_EXEMPLAR_CHARS: usize = 128;
+
+/// A counter like [`prometheus_client::metrics::exemplar::CounterWithExemplar`],
+/// which encodes the labels of its exemplar with [`Serialize`] instead of
+/// [`Encode`].
+///
+/// #### Examples
+///
+/// ```rust
+/// # use prometheus_client::{encoding::text::encode, registry::Registry};
+/// # use serde::Serialize;
+/// # use serde_prometheus_labels::CounterWithExemplar;
+/// #
+/// #[derive(Serialize)]
+/// struct Trace {
+///     trace_id: &'static str,
+/// }
+///
+/// let counter = <CounterWithExemplar<Trace>>::default();
+/// let mut registry = Registry::default();
+///
+/// registry.register("requests", "Number of requests", counter.clone());
+///
+/// counter
+///     .inc_by(1, Some(Trace { trace_id: "3c5d3f" }))
+///     .unwrap();
+///
+/// let mut serialized = vec![];
+/// encode(&mut serialized, &registry).unwrap();
+///
+/// assert_eq!(
+///     String::from_utf8(serialized).unwrap(),
+///     concat!(
+///         "# HELP requests Number of requests.\n",
+///         "# TYPE requests counter\n",
+///         "requests_total 1 # {trace_id=\"3c5d3f\"} 1\n",
+///         "# EOF\n",
+///     ),
+/// );
+/// ```
+#[cfg(not(any(target_arch = "mips", target_arch = "powerpc")))]
+#[derive(Debug)]
+pub struct CounterWithExemplar<E, N = u64, A = AtomicU64> {
+    inner: exemplar::CounterWithExemplar<Bridge<E>, N, A>,
+}
+
+#[cfg(any(target_arch = "mips", target_arch = "powerpc"))]
+#[derive(Debug)]
+pub struct CounterWithExemplar<E, N = u32, A = AtomicU32> {
+    inner: exemplar::CounterWithExemplar<Bridge<E>, N, A>,
+}
+
+impl<E, N, A> Default for CounterWithExemplar<E, N, A>
+where
+    A: Default,
+{
+    fn default() -> Self {
+        Self {
+            inner: Default::default(),
+        }
+    }
+}
+
+impl<E, N, A> CounterWithExemplar<E, N, A>
+where
+    E: Serialize,
+    N: Clone,
+    A: counter::Atomic<N>,
+{
+    /// Increases the counter by `v`, replacing its exemplar by `exemplar`
+    /// if it is `Some(_)`, and returns the previous value.
+    ///
+    /// Nothing is recorded if the exemplar fails to serialize or if the
+    /// names and values of its labels are longer than 128 chars.
+    pub fn inc_by(&self, v: N, exemplar: Option<E>) -> Result<N, Error> {
+        if let Some(exemplar) = &exemplar {
+            check_exemplar(exemplar)?;
+        }
+
+        Ok(self.inner.inc_by(v, exemplar.map(Bridge)))
+    }
+
+    /// Returns the current value of the counter.
+    pub fn get(&self) -> N {
+        self.inner.get().0
+    }
+}
+
+impl<E, N, A> EncodeMetric for CounterWithExemplar<E, N, A>
+where
+    E: Serialize,
+    N: Encode + Clone,
+    A: counter::Atomic<N>,
+{
+    fn encode(&self, encoder: Encoder) -> io::Result<()> {
+        self.inner.encode(encoder)
+    }
+
+    fn metric_type(&self) -> MetricType {
+        Self::TYPE
+    }
+}
+
+impl<E, N, A> TypedMetric for CounterWithExemplar<E, N, A> {
+    const TYPE: MetricType = <Counter<N, A> as TypedMetric>::TYPE;
+}
+
+impl<E, N, A> Clone for CounterWithExemplar<E, N, A> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// A histogram like
+/// [`prometheus_client::metrics::exemplar::HistogramWithExemplars`], which
+/// encodes the labels of its exemplars with [`Serialize`] instead of
+/// [`Encode`].
+///
+/// #### Examples
+///
+/// ```rust
+/// # use prometheus_client::{encoding::text::encode, registry::Registry};
+/// # use serde::Serialize;
+/// # use serde_prometheus_labels::{Family, HistogramConstructor, HistogramWithExemplars};
+/// #
+/// #[derive(Clone, Eq, Hash, PartialEq, Serialize)]
+/// struct Labels {
+///     host: &'static str,
+/// }
+///
+/// #[derive(Serialize)]
+/// struct Trace {
+///     trace_id: &'static str,
+/// }
+///
+/// let family = Family::<Labels, HistogramWithExemplars<Trace>, _>::new_with_constructor(
+///     HistogramConstructor::new([1.0]),
+/// );
+/// let mut registry = Registry::default();
+///
+/// registry.register("latency", "Latency of requests", family.clone());
+///
+/// family
+///     .get_or_create(&Labels {
+///         host: "unionize.org",
+///     })
+///     .observe(0.5, Some(Trace { trace_id: "3c5d3f" }))
+///     .unwrap();
+///
+/// let mut serialized = vec![];
+/// encode(&mut serialized, &registry).unwrap();
+///
+/// assert_eq!(
+///     String::from_utf8(serialized).unwrap(),
+///     concat!(
+///         "# HELP latency Latency of requests.\n",
+///         "# TYPE latency histogram\n",
+///         "latency_sum{host=\"unionize.org\"} 0.5\n",
+///         "latency_count{host=\"unionize.org\"} 1\n",
+///         "latency_bucket{host=\"unionize.org\",le=\"1.0\"} 1 # {trace_id=\"3c5d3f\"} 0.5\n",
+///         "latency_bucket{host=\"unionize.org\",le=\"+Inf\"} 1\n",
+///         "# EOF\n",
+///     ),
+/// );
+/// ```
+#[derive(Debug)]
+pub struct HistogramWithExemplars<E> {
+    inner: exemplar::HistogramWithExemplars<Bridge<E>>,
+}
+
+impl<E> HistogramWithExemplars<E> {
+    /// Creates a histogram with the given bucket upper bounds.
+    pub fn new(buckets: impl Iterator<Item = f64>) -> Self {
+        Self {
+            inner: exemplar::HistogramWithExemplars::new(buckets),
+        }
+    }
+}
+
+impl<E> HistogramWithExemplars<E>
+where
+    E: Serialize,
+{
+    /// Observes `v`, replacing the exemplar of its bucket by `exemplar` if
+    /// it is `Some(_)`.
+    ///
+    /// Nothing is recorded if the exemplar fails to serialize or if the
+    /// names and values of its labels are longer than 128 chars.
+    pub fn observe(&self, v: f64, exemplar: Option<E>) -> Result<(), Error> {
+        if let Some(exemplar) = &exemplar {
+            check_exemplar(exemplar)?;
+        }
+
+        self.inner.observe(v, exemplar.map(Bridge));
+
+        Ok(())
+    }
+}
+
+impl<E> EncodeMetric for HistogramWithExemplars<E>
+where
+    E: Serialize,
+{
+    fn encode(&self, encoder: Encoder) -> io::Result<()> {
+        self.inner.encode(encoder)
+    }
+
+    fn metric_type(&self) -> MetricType {
+        Self::TYPE
+    }
+}
+
+impl<E> TypedMetric for HistogramWithExemplars<E> {
+    const TYPE: MetricType = Histogram::TYPE;
+}
+
+impl<E> Clone for HistogramWithExemplars<E> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+fn check_exemplar(exemplar: &impl Serialize) -> Result<(), Error> {
+    let chars = crate::top::pairs(exemplar)?
+        .iter()
+        .map(|(key, value)| key.chars().count() + value.chars().count())
+        .sum::<usize>();
+
+    if chars > MAX_EXEMPLAR_CHARS {
+        return Err(exemplar_too_long(chars));
+    }
+
+    Ok(())
+}
+
+fn exemplar_too_long(chars: usize) -> Error {
+    #[derive(Debug)]
+    struct ExemplarTooLongError(usize);
+
+    impl error::Error for ExemplarTooLongError {
+        #[allow(deprecated)]
+        fn description(&self) -> &str {
+            "exemplar too long"
+        }
+    }
+
+    impl fmt::Display for ExemplarTooLongError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "exemplar too long ({} chars, at most {MAX_EXEMPLAR_CHARS} allowed)",
+                self.0,
+            )
+        }
+    }
+
+    Error::invalid_input(ExemplarTooLongError(chars))
+}
@@ -0,0 +1,496 @@
+use super::{LabelSetRef, Options, Shard};
+use crate::Error;
+use parking_lot::MappedRwLockReadGuard;
+use prometheus_client::{
+    encoding::text::{EncodeMetric, Encoder},
+    metrics::{counter::Counter, family::MetricConstructor, MetricType, TypedMetric},
+};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::hash::Hash;
+use std::io;
+use std::sync::Arc;
+
+/// A metric family like [`prometheus_client::metrics::family::Family`],
+/// which encodes its labels with [`Serialize`] instead of
+/// [`Encode`](prometheus_client::encoding::text::Encode).
+///
+/// #### Examples
+///
+/// Basic usage:
+///
+/// ```rust
+/// # use prometheus_client::{
+/// #     encoding::text::encode,
+/// #     metrics::counter::Counter,
+/// #     registry::Registry,
+/// # };
+/// # use serde::Serialize;
+/// # use serde_prometheus_labels::Family;
+/// #
+/// #[derive(Clone, Eq, Hash, PartialEq, Serialize)]
+/// struct Labels {
+///     method: Method,
+///     host: String,
+/// }
+///
+/// #[derive(Clone, Eq, Hash, PartialEq, Serialize)]
+/// enum Method {
+///     #[serde(rename = "GET")]
+///     Get,
+/// }
+///
+/// let family = <Family<Labels, Counter>>::default();
+/// let mut registry = Registry::with_prefix("http");
+///
+/// registry.register(
+///     "Incoming requests",
+///     "Number of requests per method and per host",
+///     family.clone(),
+/// );
+///
+/// family
+///     .get_or_create(&Labels {
+///         method: Method::Get,
+///         host: "unionize.org".to_string(),
+///     })
+///     .inc();
+///
+/// let mut serialized = String::new();
+///
+/// // SAFETY: We know prometheus-client only writes UTF-8 slices.
+/// unsafe {
+///     encode(&mut serialized.as_mut_vec(), &registry).unwrap();
+/// }
+///
+/// assert_eq!(
+///     serialized,
+///     concat!(
+///         "# HELP http_Incoming requests Number of requests per method and per host.\n",
+///         "# TYPE http_Incoming requests counter\n",
+///         "http_Incoming requests_total{method=\"GET\",host=\"unionize.org\"} 1\n",
+///         "# EOF\n",
+///     ),
+/// );
+/// ```
+#[derive(Debug)]
+pub struct Family<S, M, C = fn() -> M> {
+    shard: Arc<Shard<S, M>>,
+    constructor: C,
+    options: Options<S>,
+}
+
+impl<S, M, C> Family<S, M, C>
+where
+    S: Clone + Eq + Hash,
+{
+    pub fn new_with_constructor(constructor: C) -> Self {
+        Self {
+            shard: Default::default(),
+            constructor,
+            options: Default::default(),
+        }
+    }
+
+    /// Adds `const_labels` before the labels of every series of this family.
+    ///
+    /// Encoding a series fails if its label set has a key in common with
+    /// `const_labels`, unless invalid series are skipped with
+    /// [`Family::skip_invalid_series`].
+    ///
+    /// #### Examples
+    ///
+    /// ```rust
+    /// # use prometheus_client::{
+    /// #     encoding::text::encode,
+    /// #     metrics::counter::Counter,
+    /// #     registry::Registry,
+    /// # };
+    /// # use serde::Serialize;
+    /// # use serde_prometheus_labels::Family;
+    /// #
+    /// #[derive(Serialize)]
+    /// struct ConstLabels {
+    ///     region: &'static str,
+    ///     version: &'static str,
+    /// }
+    ///
+    /// #[derive(Clone, Eq, Hash, PartialEq, Serialize)]
+    /// struct Labels {
+    ///     host: String,
+    /// }
+    ///
+    /// let family = <Family<Labels, Counter>>::default()
+    ///     .with_const_labels(&ConstLabels {
+    ///         region: "eu-west-3",
+    ///         version: "0.2.0",
+    ///     })
+    ///     .unwrap();
+    /// let mut registry = Registry::default();
+    ///
+    /// registry.register("requests", "Number of requests", family.clone());
+    ///
+    /// family
+    ///     .get_or_create(&Labels {
+    ///         host: "unionize.org".to_string(),
+    ///     })
+    ///     .inc();
+    ///
+    /// let mut serialized = vec![];
+    /// encode(&mut serialized, &registry).unwrap();
+    ///
+    /// assert_eq!(
+    ///     String::from_utf8(serialized).unwrap(),
+    ///     concat!(
+    ///         "# HELP requests Number of requests.\n",
+    ///         "# TYPE requests counter\n",
+    ///         "requests_total{region=\"eu-west-3\",version=\"0.2.0\",host=\"unionize.org\"} 1\n",
+    ///         "# EOF\n",
+    ///     ),
+    /// );
+    /// ```
+    pub fn with_const_labels(mut self, const_labels: &impl Serialize) -> Result<Self, Error> {
+        self.options.set_const_labels(const_labels)?;
+
+        Ok(self)
+    }
+
+    /// Relabels the labels of every series of this family with `relabeler`
+    /// during encoding, after adding the constant labels.
+    ///
+    /// The series dropped by the relabeling rules aren't encoded. A series
+    /// given the same labels as a series encoded before it is invalid: it is
+    /// skipped if [`Family::skip_invalid_series`] was called, otherwise
+    /// encoding fails.
+    ///
+    /// #### Examples
+    ///
+    /// ```rust
+    /// # use prometheus_client::{
+    /// #     encoding::text::encode,
+    /// #     metrics::counter::Counter,
+    /// #     registry::Registry,
+    /// # };
+    /// # use serde::Serialize;
+    /// # use serde_prometheus_labels::{relabel::Relabeler, Family};
+    /// #
+    /// #[derive(Clone, Eq, Hash, PartialEq, Serialize)]
+    /// struct Labels {
+    ///     host: &'static str,
+    ///     user_id: u64,
+    /// }
+    ///
+    /// let relabeler = Relabeler::from_yaml(
+    ///     r#"
+    /// - action: labeldrop
+    ///   regex: user_id
+    /// - action: drop
+    ///   source_labels: [host]
+    ///   regex: localhost
+    /// "#,
+    /// )
+    /// .unwrap();
+    ///
+    /// let family = <Family<Labels, Counter>>::default().with_relabeler(relabeler);
+    /// let mut registry = Registry::default();
+    ///
+    /// registry.register("requests", "Number of requests", family.clone());
+    ///
+    /// for host in ["unionize.org", "localhost"] {
+    ///     family.get_or_create(&Labels { host, user_id: 42 }).inc();
+    /// }
+    ///
+    /// let mut serialized = vec![];
+    /// encode(&mut serialized, &registry).unwrap();
+    ///
+    /// assert_eq!(
+    ///     String::from_utf8(serialized).unwrap(),
+    ///     concat!(
+    ///         "# HELP requests Number of requests.\n",
+    ///         "# TYPE requests counter\n",
+    ///         "requests_total{host=\"unionize.org\"} 1\n",
+    ///         "# EOF\n",
+    ///     ),
+    /// );
+    /// ```
+    #[cfg(feature = "relabel")]
+    pub fn with_relabeler(mut self, relabeler: crate::relabel::Relabeler) -> Self {
+        self.options.set_relabeler(relabeler);
+
+        self
+    }
+
+    /// Skips the series whose label set fails to serialize during encoding,
+    /// instead of failing the whole exposition.
+    ///
+    /// Each time a series is skipped, `errors` is incremented, which should
+    /// be registered as its own metric. The series is also reported to
+    /// `on_error`, only the first time it is skipped.
+    ///
+    /// #### Examples
+    ///
+    /// ```rust
+    /// # use prometheus_client::{
+    /// #     encoding::text::encode,
+    /// #     metrics::counter::Counter,
+    /// #     registry::Registry,
+    /// # };
+    /// # use serde::Serialize;
+    /// # use serde_prometheus_labels::Family;
+    /// #
+    /// #[derive(Clone, Eq, Hash, PartialEq, Serialize)]
+    /// struct Labels {
+    ///     #[serde(rename = "the host")]
+    ///     host: String,
+    /// }
+    ///
+    /// let errors = Counter::default();
+    /// let family = <Family<Labels, Counter>>::default().skip_invalid_series(
+    ///     errors.clone(),
+    ///     |labels, error| eprintln!("skipped series for {}: {}", labels.host, error),
+    /// );
+    /// let mut registry = <Registry>::default();
+    ///
+    /// registry.register("requests", "Number of requests", Box::new(family.clone()));
+    /// registry.register("label_errors", "Number of invalid series", Box::new(errors));
+    ///
+    /// family
+    ///     .get_or_create(&Labels {
+    ///         host: "unionize.org".to_string(),
+    ///     })
+    ///     .inc();
+    ///
+    /// let mut serialized = vec![];
+    /// encode(&mut serialized, &registry).unwrap();
+    ///
+    /// assert_eq!(
+    ///     String::from_utf8(serialized).unwrap(),
+    ///     concat!(
+    ///         "# HELP requests Number of requests.\n",
+    ///         "# TYPE requests counter\n",
+    ///         "# HELP label_errors Number of invalid series.\n",
+    ///         "# TYPE label_errors counter\n",
+    ///         "label_errors_total 1\n",
+    ///         "# EOF\n",
+    ///     ),
+    /// );
+    /// ```
+    pub fn skip_invalid_series(
+        mut self,
+        errors: Counter,
+        on_error: impl Fn(&S, &Error) + Send + Sync + 'static,
+    ) -> Self {
+        self.options.skip_invalid_series(errors, on_error);
+
+        self
+    }
+
+    /// Encodes the series of this family sorted by their serialized label
+    /// set, instead of in an unspecified order.
+    ///
+    /// #### Examples
+    ///
+    /// ```rust
+    /// # use prometheus_client::{
+    /// #     encoding::text::encode,
+    /// #     metrics::counter::Counter,
+    /// #     registry::Registry,
+    /// # };
+    /// # use serde::Serialize;
+    /// # use serde_prometheus_labels::Family;
+    /// #
+    /// #[derive(Clone, Eq, Hash, PartialEq, Serialize)]
+    /// struct Labels {
+    ///     host: &'static str,
+    /// }
+    ///
+    /// let family = <Family<Labels, Counter>>::default().sort_by_labels();
+    /// let mut registry = Registry::default();
+    ///
+    /// registry.register("requests", "Number of requests", family.clone());
+    ///
+    /// for host in ["unionize.org", "amazon.com", "starbucks.com"] {
+    ///     family.get_or_create(&Labels { host }).inc();
+    /// }
+    ///
+    /// let mut serialized = vec![];
+    /// encode(&mut serialized, &registry).unwrap();
+    ///
+    /// assert_eq!(
+    ///     String::from_utf8(serialized).unwrap(),
+    ///     concat!(
+    ///         "# HELP requests Number of requests.\n",
+    ///         "# TYPE requests counter\n",
+    ///         "requests_total{host=\"amazon.com\"} 1\n",
+    ///         "requests_total{host=\"starbucks.com\"} 1\n",
+    ///         "requests_total{host=\"unionize.org\"} 1\n",
+    ///         "# EOF\n",
+    ///     ),
+    /// );
+    /// ```
+    pub fn sort_by_labels(mut self) -> Self {
+        self.options.sort_by_labels();
+
+        self
+    }
+
+    /// Encodes the series of this family sorted by their label set with
+    /// `compare`, instead of in an unspecified order.
+    pub fn sort_by(mut self, compare: impl Fn(&S, &S) -> Ordering + Send + Sync + 'static) -> Self {
+        self.options.sort_by(compare);
+
+        self
+    }
+}
+
+impl<S, M> Default for Family<S, M>
+where
+    S: Clone + Eq + Hash,
+    M: Default,
+{
+    fn default() -> Self {
+        Self::new_with_constructor(M::default)
+    }
+}
+
+impl<S, M, C> Family<S, M, C>
+where
+    S: Clone + Eq + Hash,
+    C: MetricConstructor<M>,
+{
+    /// Returns the metric of the series with the given label set, creating
+    /// it if needed.
+    ///
+    /// The label set can be looked up through any [`LabelSetRef<S>`],
+    /// which is only converted to an owned `S` when the series is created.
+    pub fn get_or_create<Q>(&self, label_set: &Q) -> MappedRwLockReadGuard<'_, M>
+    where
+        Q: ?Sized + LabelSetRef<S>,
+    {
+        self.shard.get_or_create(label_set, &self.constructor)
+    }
+
+    /// Like [`Family::get_or_create`], but returns an owned handle to the
+    /// metric, which doesn't hold any lock on the family.
+    ///
+    /// #### Examples
+    ///
+    /// ```rust
+    /// # use prometheus_client::metrics::counter::Counter;
+    /// # use serde::Serialize;
+    /// # use serde_prometheus_labels::Family;
+    /// # use std::sync::Arc;
+    /// #
+    /// #[derive(Clone, Eq, Hash, PartialEq, Serialize)]
+    /// struct Labels {
+    ///     host: String,
+    /// }
+    ///
+    /// struct Handler {
+    ///     requests: Arc<Counter>,
+    /// }
+    ///
+    /// let family = <Family<Labels, Counter>>::default();
+    ///
+    /// let handler = Handler {
+    ///     requests: family.get_or_create_owned(&Labels {
+    ///         host: "unionize.org".to_string(),
+    ///     }),
+    /// };
+    ///
+    /// handler.requests.inc();
+    ///
+    /// let requests = family.get_or_create(&Labels {
+    ///     host: "unionize.org".to_string(),
+    /// });
+    ///
+    /// assert_eq!(requests.get(), 1);
+    /// ```
+    pub fn get_or_create_owned<Q>(&self, label_set: &Q) -> Arc<M>
+    where
+        Q: ?Sized + LabelSetRef<S>,
+    {
+        self.shard.get_or_create_owned(label_set, &self.constructor)
+    }
+}
+
+impl<S, M, C> EncodeMetric for Family<S, M, C>
+where
+    S: Clone + Eq + Hash + Serialize,
+    M: EncodeMetric + TypedMetric,
+    C: MetricConstructor<M>,
+{
+    fn encode(&self, mut encoder: Encoder) -> io::Result<()> {
+        self.encode_with(&mut encoder, |metric| metric)
+    }
+
+    fn metric_type(&self) -> MetricType {
+        M::TYPE
+    }
+}
+
+impl<S, M, C> Family<S, M, C>
+where
+    S: Serialize,
+{
+    /// Encodes the series of this family, with `project` returning what to
+    /// encode for each of them.
+    pub(super) fn encode_with<N>(
+        &self,
+        encoder: &mut Encoder,
+        project: impl Fn(&M) -> &N,
+    ) -> io::Result<()>
+    where
+        N: ?Sized + EncodeMetric,
+    {
+        self.options.encode(
+            encoder,
+            self.shard
+                .read()
+                .iter()
+                .map(|(label_set, series)| (label_set, project(&series.metric), &series.reported)),
+        )
+    }
+}
+
+impl<S, M, C> TypedMetric for Family<S, M, C>
+where
+    M: TypedMetric,
+{
+    const TYPE: MetricType = <M as TypedMetric>::TYPE;
+}
+
+impl<S, M, C> Clone for Family<S, M, C>
+where
+    C: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            shard: self.shard.clone(),
+            constructor: self.constructor.clone(),
+            options: self.options.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "protobuf")]
+impl<S, M, C> super::protobuf::EncodeProtobuf for Family<S, M, C>
+where
+    S: Clone + Eq + Hash + Serialize,
+    M: super::protobuf::EncodeProtobuf,
+{
+    fn encode_protobuf(
+        &self,
+        labels: &[super::protobuf::LabelPair],
+        metrics: &mut Vec<super::protobuf::Metric>,
+    ) -> Result<(), Error> {
+        self.options.encode_protobuf(
+            labels,
+            metrics,
+            self.shard
+                .read()
+                .iter()
+                .map(|(label_set, series)| (label_set, &*series.metric, &series.reported)),
+        )
+    }
+}
@@ -0,0 +1,182 @@
+use super::Family;
+use prometheus_client::{
+    encoding::text::{EncodeMetric, Encoder},
+    metrics::{MetricType, TypedMetric},
+};
+use serde::Serialize;
+use std::hash::Hash;
+use std::io;
+
+/// A [`Family`] whose series each hold a group of metrics, such as
+/// `(Counter, Counter, Histogram)`.
+///
+/// A single lookup returns all the metrics of a series, but each of them is
+/// registered as its own metric family through [`Family::member`].
+///
+/// #### Examples
+///
+/// ```rust
+/// # use prometheus_client::{
+/// #     encoding::text::encode,
+/// #     metrics::{counter::Counter, gauge::Gauge},
+/// #     registry::Registry,
+/// # };
+/// # use serde::Serialize;
+/// # use serde_prometheus_labels::FamilyGroup;
+/// #
+/// #[derive(Clone, Eq, Hash, PartialEq, Serialize)]
+/// struct Labels {
+///     host: String,
+/// }
+///
+/// let group = <FamilyGroup<Labels, (Counter, Counter, Gauge)>>::default();
+/// let mut registry = <Registry>::default();
+///
+/// registry.register("requests", "Number of requests", Box::new(group.member(0)));
+/// registry.register("errors", "Number of errors", Box::new(group.member(1)));
+/// registry.register("in_flight", "Number of requests in flight", Box::new(group.member(2)));
+///
+/// let metrics = group.get_or_create(&Labels {
+///     host: "unionize.org".to_string(),
+/// });
+/// let (requests, _, in_flight) = &*metrics;
+///
+/// requests.inc();
+/// in_flight.inc();
+///
+/// drop(metrics);
+///
+/// let mut serialized = vec![];
+/// encode(&mut serialized, &registry).unwrap();
+///
+/// assert_eq!(
+///     String::from_utf8(serialized).unwrap(),
+///     concat!(
+///         "# HELP requests Number of requests.\n",
+///         "# TYPE requests counter\n",
+///         "requests_total{host=\"unionize.org\"} 1\n",
+///         "# HELP errors Number of errors.\n",
+///         "# TYPE errors counter\n",
+///         "errors_total{host=\"unionize.org\"} 0\n",
+///         "# HELP in_flight Number of requests in flight.\n",
+///         "# TYPE in_flight gauge\n",
+///         "in_flight{host=\"unionize.org\"} 1\n",
+///         "# EOF\n",
+///     ),
+/// );
+/// ```
+pub type FamilyGroup<S, G, C = fn() -> G> = Family<S, G, C>;
+
+/// A group of metrics stored together in the series of a [`FamilyGroup`].
+///
+/// This is implemented for tuples of up to 8 metrics.
+pub trait MetricGroup {
+    /// The number of metrics in the group.
+    const LEN: usize;
+
+    /// Returns the metric at `index`.
+    ///
+    /// Panics if `index` is out of bounds.
+    fn metric(&self, index: usize) -> &(dyn EncodeMetric + 'static);
+
+    /// Returns the type of the metric at `index`.
+    ///
+    /// Panics if `index` is out of bounds.
+    fn metric_type(index: usize) -> MetricType;
+}
+
+macro_rules! metric_groups {
+    ($(($($index:tt: $ty:ident),+))+) => {$(
+        impl<$($ty),+> MetricGroup for ($($ty,)+)
+        where
+            $($ty: 'static + EncodeMetric + TypedMetric,)+
+        {
+            const LEN: usize = [$($index),+].len();
+
+            fn metric(&self, index: usize) -> &(dyn EncodeMetric + 'static) {
+                match index {
+                    $($index => &self.$index,)+
+                    _ => out_of_bounds(index, Self::LEN),
+                }
+            }
+
+            fn metric_type(index: usize) -> MetricType {
+                match index {
+                    $($index => $ty::TYPE,)+
+                    _ => out_of_bounds(index, Self::LEN),
+                }
+            }
+        }
+    )+}
+}
+
+metric_groups! {
+    (0: A)
+    (0: A, 1: B)
+    (0: A, 1: B, 2: C)
+    (0: A, 1: B, 2: C, 3: D)
+    (0: A, 1: B, 2: C, 3: D, 4: E)
+    (0: A, 1: B, 2: C, 3: D, 4: E, 5: F)
+    (0: A, 1: B, 2: C, 3: D, 4: E, 5: F, 6: G)
+    (0: A, 1: B, 2: C, 3: D, 4: E, 5: F, 6: G, 7: H)
+}
+
+fn out_of_bounds(index: usize, len: usize) -> ! {
+    panic!("metric index {index} out of bounds for a group of {len} metrics")
+}
+
+impl<S, G, C> Family<S, G, C>
+where
+    S: Clone + Eq + Hash,
+    G: MetricGroup,
+    C: Clone,
+{
+    /// Returns the metric at `index` in the groups of this family, to be
+    /// registered as its own metric family.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn member(&self, index: usize) -> GroupMember<S, G, C> {
+        if index >= G::LEN {
+            out_of_bounds(index, G::LEN);
+        }
+
+        GroupMember {
+            group: self.clone(),
+            index,
+        }
+    }
+}
+
+/// One of the metrics of a [`FamilyGroup`], returned by [`Family::member`].
+#[derive(Debug)]
+pub struct GroupMember<S, G, C = fn() -> G> {
+    group: Family<S, G, C>,
+    index: usize,
+}
+
+impl<S, G, C> Clone for GroupMember<S, G, C>
+where
+    C: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            group: self.group.clone(),
+            index: self.index,
+        }
+    }
+}
+
+impl<S, G, C> EncodeMetric for GroupMember<S, G, C>
+where
+    S: Clone + Eq + Hash + Serialize,
+    G: MetricGroup,
+{
+    fn encode(&self, mut encoder: Encoder) -> io::Result<()> {
+        self.group
+            .encode_with(&mut encoder, |group| group.metric(self.index))
+    }
+
+    fn metric_type(&self) -> MetricType {
+        G::metric_type(self.index)
+    }
+}
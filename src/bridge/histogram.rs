@@ -0,0 +1,262 @@
+use super::{Family, HistogramWithExemplars};
+use parking_lot::RwLock;
+use prometheus_client::{
+    encoding::text::{EncodeMetric, Encoder},
+    metrics::{
+        family::MetricConstructor,
+        histogram::{self, exponential_buckets, linear_buckets},
+        MetricType, TypedMetric,
+    },
+};
+use std::hash::Hash;
+use std::sync::Arc;
+use std::{io, iter};
+
+impl<S> Family<S, Histogram, HistogramConstructor>
+where
+    S: Clone + Eq + Hash,
+{
+    /// Creates a family of histograms with the given bucket upper bounds.
+    ///
+    /// The bounds are sorted and deduplicated, and non-finite ones are
+    /// ignored, as the `+Inf` bucket is always added last. So is
+    /// [`f64::MAX`], which prometheus-client uses as the upper bound of that
+    /// `+Inf` bucket. They are written as `le` labels in the canonical
+    /// format of OpenMetrics floats.
+    ///
+    /// #### Examples
+    ///
+    /// ```rust
+    /// # use prometheus_client::{encoding::text::encode, registry::Registry};
+    /// # use serde::Serialize;
+    /// # use serde_prometheus_labels::Family;
+    /// #
+    /// #[derive(Clone, Eq, Hash, PartialEq, Serialize)]
+    /// struct Labels {
+    ///     host: String,
+    /// }
+    ///
+    /// let family = Family::histogram([0.5, 1.0, f64::INFINITY]);
+    /// let mut registry = Registry::default();
+    ///
+    /// registry.register("latency", "Latency of requests", family.clone());
+    ///
+    /// family
+    ///     .get_or_create(&Labels {
+    ///         host: "unionize.org".to_string(),
+    ///     })
+    ///     .observe(0.75);
+    ///
+    /// let mut serialized = vec![];
+    /// encode(&mut serialized, &registry).unwrap();
+    ///
+    /// assert_eq!(
+    ///     String::from_utf8(serialized).unwrap(),
+    ///     concat!(
+    ///         "# HELP latency Latency of requests.\n",
+    ///         "# TYPE latency histogram\n",
+    ///         "latency_sum{host=\"unionize.org\"} 0.75\n",
+    ///         "latency_count{host=\"unionize.org\"} 1\n",
+    ///         "latency_bucket{host=\"unionize.org\",le=\"0.5\"} 0\n",
+    ///         "latency_bucket{host=\"unionize.org\",le=\"1.0\"} 1\n",
+    ///         "latency_bucket{host=\"unionize.org\",le=\"+Inf\"} 1\n",
+    ///         "# EOF\n",
+    ///     ),
+    /// );
+    /// ```
+    pub fn histogram(buckets: impl IntoIterator<Item = f64>) -> Self {
+        Self::new_with_constructor(HistogramConstructor::new(buckets))
+    }
+
+    /// Creates a family of histograms with `length` buckets, the first one
+    /// having `start` as upper bound and each next one `factor` times the
+    /// upper bound of the previous one.
+    pub fn exponential_histogram(start: f64, factor: f64, length: u16) -> Self {
+        Self::histogram(exponential_buckets(start, factor, length))
+    }
+
+    /// Creates a family of histograms with `length` buckets, the first one
+    /// having `start` as upper bound and each next one `width` more than the
+    /// upper bound of the previous one.
+    pub fn linear_histogram(start: f64, width: f64, length: u16) -> Self {
+        Self::histogram(linear_buckets(start, width, length))
+    }
+}
+
+/// Creates the histograms of a family created with [`Family::histogram`].
+///
+/// It can also create the histograms of prometheus-client and
+/// [`HistogramWithExemplars`] for families created with
+/// [`Family::new_with_constructor`].
+#[derive(Clone, Debug)]
+pub struct HistogramConstructor {
+    buckets: Arc<[f64]>,
+}
+
+impl HistogramConstructor {
+    /// Creates a constructor of histograms with the given bucket upper
+    /// bounds, which are handled as in [`Family::histogram`].
+    pub fn new(buckets: impl IntoIterator<Item = f64>) -> Self {
+        // prometheus-client appends an `f64::MAX` bucket, which it encodes
+        // as `+Inf`, so an explicit `f64::MAX` bound would duplicate it.
+        let mut buckets = buckets
+            .into_iter()
+            .filter(|bucket| bucket.is_finite() && *bucket != f64::MAX)
+            .collect::<Vec<_>>();
+
+        buckets.sort_by(f64::total_cmp);
+        buckets.dedup();
+
+        Self {
+            buckets: buckets.into(),
+        }
+    }
+}
+
+impl MetricConstructor<Histogram> for HistogramConstructor {
+    fn new_metric(&self) -> Histogram {
+        Histogram::new(self.buckets.iter().copied())
+    }
+}
+
+impl MetricConstructor<histogram::Histogram> for HistogramConstructor {
+    fn new_metric(&self) -> histogram::Histogram {
+        histogram::Histogram::new(self.buckets.iter().copied())
+    }
+}
+
+impl<E> MetricConstructor<HistogramWithExemplars<E>> for HistogramConstructor {
+    fn new_metric(&self) -> HistogramWithExemplars<E> {
+        HistogramWithExemplars::new(self.buckets.iter().copied())
+    }
+}
+
+/// A histogram like [`prometheus_client::metrics::histogram::Histogram`],
+/// whose buckets can be read when encoding it in the
+/// [protobuf](crate::protobuf) format.
+///
+/// This is the metric of the families created with [`Family::histogram`].
+#[derive(Clone, Debug)]
+pub struct Histogram {
+    inner: Arc<RwLock<HistogramInner>>,
+}
+
+#[derive(Debug)]
+struct HistogramInner {
+    sum: f64,
+    count: u64,
+    /// The upper bounds of the buckets with their own counts, the last one
+    /// being the `+Inf` bucket.
+    buckets: Vec<(f64, u64)>,
+}
+
+impl Histogram {
+    /// Creates a histogram with the given bucket upper bounds, followed by
+    /// the `+Inf` bucket.
+    pub fn new(buckets: impl Iterator<Item = f64>) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(HistogramInner {
+                sum: 0.0,
+                count: 0,
+                buckets: buckets
+                    .chain(iter::once(f64::MAX))
+                    .map(|upper_bound| (upper_bound, 0))
+                    .collect(),
+            })),
+        }
+    }
+
+    /// Observes `v`.
+    pub fn observe(&self, v: f64) {
+        let mut inner = self.inner.write();
+
+        inner.sum += v;
+        inner.count += 1;
+
+        if let Some((_, count)) = inner
+            .buckets
+            .iter_mut()
+            .find(|(upper_bound, _)| *upper_bound >= v)
+        {
+            *count += 1;
+        }
+    }
+}
+
+impl EncodeMetric for Histogram {
+    fn encode(&self, mut encoder: Encoder) -> io::Result<()> {
+        let inner = self.inner.read();
+
+        encoder
+            .encode_suffix("sum")?
+            .no_bucket()?
+            .encode_value(inner.sum)?
+            .no_exemplar()?;
+        encoder
+            .encode_suffix("count")?
+            .no_bucket()?
+            .encode_value(inner.count)?
+            .no_exemplar()?;
+
+        let mut cumulative_count = 0;
+
+        for &(upper_bound, count) in &inner.buckets {
+            cumulative_count += count;
+
+            encoder
+                .encode_suffix("bucket")?
+                .encode_bucket(upper_bound)?
+                .encode_value(cumulative_count)?
+                .no_exemplar()?;
+        }
+
+        Ok(())
+    }
+
+    fn metric_type(&self) -> MetricType {
+        Self::TYPE
+    }
+}
+
+impl TypedMetric for Histogram {
+    const TYPE: MetricType = MetricType::Histogram;
+}
+
+#[cfg(feature = "protobuf")]
+impl super::protobuf::EncodeProtobuf for Histogram {
+    fn encode_protobuf(
+        &self,
+        labels: &[super::protobuf::LabelPair],
+        metrics: &mut Vec<super::protobuf::Metric>,
+    ) -> Result<(), crate::Error> {
+        let inner = self.inner.read();
+        let mut cumulative_count = 0;
+
+        // The +Inf bucket is implied by the sample count.
+        let bucket = inner.buckets[..inner.buckets.len() - 1]
+            .iter()
+            .map(|&(upper_bound, count)| {
+                cumulative_count += count;
+
+                super::protobuf::Bucket {
+                    cumulative_count: Some(cumulative_count),
+                    upper_bound: Some(upper_bound),
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        metrics.push(super::protobuf::Metric {
+            label: labels.to_vec(),
+            histogram: Some(super::protobuf::Histogram {
+                sample_count: Some(inner.count),
+                sample_sum: Some(inner.sum),
+                bucket,
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+use super::Bridge;
+use prometheus_client::{
+    encoding::text::{EncodeMetric, Encoder},
+    metrics::{MetricType, TypedMetric},
+};
+use serde::Serialize;
+use std::io;
+
+/// An info metric like [`prometheus_client::metrics::info::Info`], which
+/// encodes its labels with [`Serialize`] instead of
+/// [`Encode`](prometheus_client::encoding::text::Encode).
+///
+/// It is exposed as a gauge with a single sample of value 1, so that it can
+/// be scraped with the Prometheus text format, which has no info type. Its
+/// name should thus end with `_info`.
+///
+/// #### Examples
+///
+/// ```rust
+/// # use prometheus_client::{encoding::text::encode, registry::Registry};
+/// # use serde::Serialize;
+/// # use serde_prometheus_labels::Info;
+/// #
+/// #[derive(Serialize)]
+/// struct BuildInfo {
+///     version: &'static str,
+///     git_sha: &'static str,
+///     rustc: &'static str,
+/// }
+///
+/// let mut registry = Registry::default();
+///
+/// registry.register(
+///     "build_info",
+///     "Build information",
+///     Info::new(BuildInfo {
+///         version: "0.2.0",
+///         git_sha: "f44b9bb",
+///         rustc: "1.65.0",
+///     }),
+/// );
+///
+/// let mut serialized = vec![];
+/// encode(&mut serialized, &registry).unwrap();
+///
+/// assert_eq!(
+///     String::from_utf8(serialized).unwrap(),
+///     concat!(
+///         "# HELP build_info Build information.\n",
+///         "# TYPE build_info gauge\n",
+///         "build_info{version=\"0.2.0\",git_sha=\"f44b9bb\",rustc=\"1.65.0\"} 1\n",
+///         "# EOF\n",
+///     ),
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct Info<S>(S);
+
+impl<S> Info<S> {
+    pub fn new(label_set: S) -> Self {
+        Self(label_set)
+    }
+}
+
+impl<S> EncodeMetric for Info<S>
+where
+    S: Serialize,
+{
+    fn encode(&self, mut encoder: Encoder) -> io::Result<()> {
+        encoder
+            .with_label_set(Bridge::from_ref(&self.0))
+            .no_suffix()?
+            .no_bucket()?
+            .encode_value(1u32)?
+            .no_exemplar()
+    }
+
+    fn metric_type(&self) -> MetricType {
+        Self::TYPE
+    }
+}
+
+impl<S> TypedMetric for Info<S> {
+    const TYPE: MetricType = MetricType::Gauge;
+}
+
+#[cfg(feature = "protobuf")]
+impl<S> super::protobuf::EncodeProtobuf for Info<S>
+where
+    S: Serialize,
+{
+    fn encode_protobuf(
+        &self,
+        labels: &[super::protobuf::LabelPair],
+        metrics: &mut Vec<super::protobuf::Metric>,
+    ) -> Result<(), crate::Error> {
+        let mut labels = labels.to_vec();
+        super::protobuf::push_label_pairs(&mut labels, &self.0)?;
+
+        metrics.push(super::protobuf::Metric {
+            label: labels,
+            gauge: Some(super::protobuf::Gauge { value: Some(1.0) }),
+            ..Default::default()
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,686 @@
+use crate::Error;
+use hashbrown::{Equivalent, HashMap};
+use parking_lot::{MappedRwLockReadGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use prometheus_client::{
+    encoding::text::{Encode, EncodeMetric, Encoder},
+    metrics::{counter::Counter, family::MetricConstructor},
+};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::Arc;
+use std::{fmt, io};
+
+mod collector;
+mod exemplar;
+mod family;
+mod group;
+mod histogram;
+mod info;
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+mod sharded;
+mod textfile;
+
+pub use self::collector::Collector;
+pub use self::exemplar::{CounterWithExemplar, HistogramWithExemplars};
+pub use self::family::Family;
+pub use self::group::{FamilyGroup, GroupMember, MetricGroup};
+pub use self::histogram::{Histogram, HistogramConstructor};
+pub use self::info::Info;
+pub use self::sharded::ShardedFamily;
+pub use self::textfile::TextfileWriter;
+
+/// A borrowed form of the label set `S`, with which series of a [`Family`]
+/// can be looked up without constructing an `S`.
+///
+/// Implementations must hash exactly like the label sets they are
+/// equivalent to.
+///
+/// #### Examples
+///
+/// ```rust
+/// # use prometheus_client::metrics::counter::Counter;
+/// # use serde::Serialize;
+/// # use serde_prometheus_labels::{Family, LabelSetRef};
+/// #
+/// #[derive(Clone, Eq, Hash, PartialEq, Serialize)]
+/// struct Labels {
+///     method: Method,
+///     host: String,
+/// }
+///
+/// #[derive(Hash)]
+/// struct LabelsRef<'a> {
+///     method: Method,
+///     host: &'a str,
+/// }
+///
+/// #[derive(Clone, Copy, Eq, Hash, PartialEq, Serialize)]
+/// enum Method {
+///     #[serde(rename = "GET")]
+///     Get,
+/// }
+///
+/// impl LabelSetRef<Labels> for LabelsRef<'_> {
+///     fn equivalent(&self, label_set: &Labels) -> bool {
+///         self.method == label_set.method && self.host == label_set.host
+///     }
+///
+///     fn to_label_set(&self) -> Labels {
+///         Labels {
+///             method: self.method,
+///             host: self.host.to_owned(),
+///         }
+///     }
+/// }
+///
+/// let family = <Family<Labels, Counter>>::default();
+///
+/// family
+///     .get_or_create(&LabelsRef {
+///         method: Method::Get,
+///         host: "unionize.org",
+///     })
+///     .inc();
+///
+/// let requests = family.get_or_create(&Labels {
+///     method: Method::Get,
+///     host: "unionize.org".to_string(),
+/// });
+///
+/// assert_eq!(requests.get(), 1);
+/// ```
+pub trait LabelSetRef<S>: Hash {
+    /// Returns whether `self` is equivalent to `label_set`.
+    fn equivalent(&self, label_set: &S) -> bool;
+
+    /// Converts `self` to an owned label set.
+    fn to_label_set(&self) -> S;
+}
+
+impl<S> LabelSetRef<S> for S
+where
+    S: Clone + Eq + Hash,
+{
+    fn equivalent(&self, label_set: &S) -> bool {
+        self == label_set
+    }
+
+    fn to_label_set(&self) -> S {
+        self.clone()
+    }
+}
+
+/// Adapts a [`LabelSetRef<S>`] to the lookups of the inner map.
+struct Lookup<'a, Q>(&'a Q)
+where
+    Q: ?Sized;
+
+impl<Q> Hash for Lookup<'_, Q>
+where
+    Q: ?Sized + Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl<S, Q> Equivalent<S> for Lookup<'_, Q>
+where
+    Q: ?Sized + LabelSetRef<S>,
+{
+    fn equivalent(&self, label_set: &S) -> bool {
+        self.0.equivalent(label_set)
+    }
+}
+
+/// The series of a family, or of one of its shards.
+#[derive(Debug)]
+struct Shard<S, M> {
+    metrics: RwLock<HashMap<S, Series<M>, RandomState>>,
+}
+
+/// The metric of a series.
+#[derive(Debug)]
+struct Series<M> {
+    metric: Arc<M>,
+    /// Whether the label set of this series was already reported as invalid.
+    reported: AtomicBool,
+}
+
+impl<M> Series<M> {
+    fn new(metric: Arc<M>) -> Self {
+        Self {
+            metric,
+            reported: AtomicBool::new(false),
+        }
+    }
+}
+
+impl<S, M> Default for Shard<S, M> {
+    fn default() -> Self {
+        Self {
+            metrics: Default::default(),
+        }
+    }
+}
+
+impl<S, M> Shard<S, M>
+where
+    S: Eq + Hash,
+{
+    fn get_or_create<Q>(
+        &self,
+        label_set: &Q,
+        constructor: &impl MetricConstructor<M>,
+    ) -> MappedRwLockReadGuard<'_, M>
+    where
+        Q: ?Sized + LabelSetRef<S>,
+    {
+        if let Ok(metric) = RwLockReadGuard::try_map(self.metrics.read(), |metrics| {
+            metrics
+                .get(&Lookup(label_set))
+                .map(|series| &*series.metric)
+        }) {
+            return metric;
+        }
+
+        let mut metrics = self.metrics.write();
+
+        if !metrics.contains_key(&Lookup(label_set)) {
+            metrics.insert(
+                label_set.to_label_set(),
+                Series::new(Arc::new(constructor.new_metric())),
+            );
+        }
+
+        RwLockReadGuard::map(RwLockWriteGuard::downgrade(metrics), |metrics| {
+            &*metrics[&Lookup(label_set)].metric
+        })
+    }
+
+    fn get_or_create_owned<Q>(
+        &self,
+        label_set: &Q,
+        constructor: &impl MetricConstructor<M>,
+    ) -> Arc<M>
+    where
+        Q: ?Sized + LabelSetRef<S>,
+    {
+        if let Some(series) = self.metrics.read().get(&Lookup(label_set)) {
+            return series.metric.clone();
+        }
+
+        let mut metrics = self.metrics.write();
+
+        if let Some(series) = metrics.get(&Lookup(label_set)) {
+            return series.metric.clone();
+        }
+
+        let metric = Arc::new(constructor.new_metric());
+        metrics.insert(label_set.to_label_set(), Series::new(metric.clone()));
+
+        metric
+    }
+}
+
+impl<S, M> Shard<S, M> {
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<S, Series<M>, RandomState>> {
+        self.metrics.read()
+    }
+}
+
+/// How the label sets of a family are encoded.
+#[derive(Debug)]
+struct Options<S> {
+    const_labels: Option<Arc<ConstLabels>>,
+    invalid_series: Option<InvalidSeries<S>>,
+    order: Order<S>,
+    #[cfg(feature = "relabel")]
+    relabeler: Option<Arc<crate::relabel::Relabeler>>,
+}
+
+impl<S> Default for Options<S> {
+    fn default() -> Self {
+        Self {
+            const_labels: None,
+            invalid_series: None,
+            order: Order::Unspecified,
+            #[cfg(feature = "relabel")]
+            relabeler: None,
+        }
+    }
+}
+
+impl<S> Clone for Options<S> {
+    fn clone(&self) -> Self {
+        Self {
+            const_labels: self.const_labels.clone(),
+            invalid_series: self.invalid_series.clone(),
+            order: self.order.clone(),
+            #[cfg(feature = "relabel")]
+            relabeler: self.relabeler.clone(),
+        }
+    }
+}
+
+impl<S> Options<S> {
+    fn set_const_labels(&mut self, const_labels: &impl Serialize) -> Result<(), Error> {
+        let pairs = crate::top::pairs(const_labels)?;
+
+        self.const_labels = Some(Arc::new(ConstLabels {
+            serialized: crate::to_string(const_labels)?,
+            keys: pairs.iter().map(|(key, _)| key.clone()).collect(),
+            #[cfg(any(feature = "protobuf", feature = "relabel"))]
+            pairs,
+        }));
+
+        Ok(())
+    }
+
+    fn skip_invalid_series(
+        &mut self,
+        errors: Counter,
+        on_error: impl Fn(&S, &Error) + Send + Sync + 'static,
+    ) {
+        self.invalid_series = Some(InvalidSeries {
+            errors,
+            on_error: Arc::new(on_error),
+        });
+    }
+
+    fn sort_by_labels(&mut self) {
+        self.order = Order::Labels;
+    }
+
+    fn sort_by(&mut self, compare: impl Fn(&S, &S) -> Ordering + Send + Sync + 'static) {
+        self.order = Order::By(Arc::new(compare));
+    }
+
+    #[cfg(feature = "relabel")]
+    fn set_relabeler(&mut self, relabeler: crate::relabel::Relabeler) {
+        self.relabeler = Some(Arc::new(relabeler));
+    }
+
+    fn is_ordered(&self) -> bool {
+        !matches!(self.order, Order::Unspecified)
+    }
+
+    /// Returns whether label sets are encoded as is, without going through
+    /// [`Options::serialize_label_set`].
+    fn is_plain(&self) -> bool {
+        #[cfg(feature = "relabel")]
+        if self.relabeler.is_some() {
+            return false;
+        }
+
+        self.const_labels.is_none() && self.invalid_series.is_none()
+    }
+}
+
+impl<S> Options<S>
+where
+    S: Serialize,
+{
+    fn encode<'a, M>(
+        &self,
+        encoder: &mut Encoder,
+        series: impl IntoIterator<Item = (&'a S, &'a M, &'a AtomicBool)>,
+    ) -> io::Result<()>
+    where
+        S: 'a,
+        M: 'a + ?Sized + EncodeMetric,
+    {
+        self.encode_ordered(encoder, series, self.is_plain())
+    }
+
+    /// Like [`Options::encode`], but always serializes label sets into a
+    /// buffer first, so that a label set failing to serialize never leaves a
+    /// partially written line.
+    fn encode_buffered<'a, M>(
+        &self,
+        encoder: &mut Encoder,
+        series: impl IntoIterator<Item = (&'a S, &'a M, &'a AtomicBool)>,
+    ) -> io::Result<()>
+    where
+        S: 'a,
+        M: 'a + ?Sized + EncodeMetric,
+    {
+        self.encode_ordered(encoder, series, false)
+    }
+
+    fn encode_ordered<'a, M>(
+        &self,
+        encoder: &mut Encoder,
+        series: impl IntoIterator<Item = (&'a S, &'a M, &'a AtomicBool)>,
+        plain: bool,
+    ) -> io::Result<()>
+    where
+        S: 'a,
+        M: 'a + ?Sized + EncodeMetric,
+    {
+        let compare = match &self.order {
+            Order::Unspecified => return self.encode_in_order(encoder, series, plain),
+            Order::Labels => return self.encode_sorted_by_labels(encoder, series),
+            Order::By(compare) => compare,
+        };
+
+        let mut series = series.into_iter().collect::<Vec<_>>();
+        series.sort_by(|(a, ..), (b, ..)| compare(a, b));
+
+        self.encode_in_order(encoder, series, plain)
+    }
+
+    fn encode_in_order<'a, M>(
+        &self,
+        encoder: &mut Encoder,
+        series: impl IntoIterator<Item = (&'a S, &'a M, &'a AtomicBool)>,
+        plain: bool,
+    ) -> io::Result<()>
+    where
+        S: 'a,
+        M: 'a + ?Sized + EncodeMetric,
+    {
+        if plain {
+            for (label_set, metric, _) in series {
+                metric.encode(encoder.with_label_set(Bridge::from_ref(label_set)))?;
+            }
+
+            return Ok(());
+        }
+
+        let mut buf = vec![];
+        let mut encoded = self.encoded_series();
+
+        for (label_set, metric, reported) in series {
+            buf.clear();
+
+            let serialized = self
+                .serialize_label_set(&mut buf, label_set)
+                .and_then(|kept| check_unique(&mut encoded, kept, &buf));
+
+            match serialized {
+                Ok(true) => metric.encode(encoder.with_label_set(&Serialized(&buf)))?,
+                Ok(false) => {}
+                Err(error) => self.invalid_series(label_set, reported, error)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn encode_sorted_by_labels<'a, M>(
+        &self,
+        encoder: &mut Encoder,
+        series: impl IntoIterator<Item = (&'a S, &'a M, &'a AtomicBool)>,
+    ) -> io::Result<()>
+    where
+        S: 'a,
+        M: 'a + ?Sized + EncodeMetric,
+    {
+        let mut series = series
+            .into_iter()
+            .map(|(label_set, metric, reported)| {
+                let mut buf = vec![];
+                let serialized = self.serialize_label_set(&mut buf, label_set);
+
+                (
+                    serialized.map(|kept| kept.then_some(buf)),
+                    label_set,
+                    metric,
+                    reported,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        series.sort_by(|(a, ..), (b, ..)| a.as_ref().ok().cmp(&b.as_ref().ok()));
+
+        let mut encoded = self.encoded_series();
+
+        for (serialized, label_set, metric, reported) in series {
+            let serialized = serialized.and_then(|buf| match buf {
+                Some(buf) => check_unique(&mut encoded, true, &buf).map(|_| Some(buf)),
+                None => Ok(None),
+            });
+
+            match serialized {
+                Ok(Some(buf)) => metric.encode(encoder.with_label_set(&Serialized(&buf)))?,
+                Ok(None) => {}
+                Err(error) => self.invalid_series(label_set, reported, error)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Skips an invalid series, counting every skip but reporting it to
+    /// `on_error` only the first time.
+    fn invalid_series(&self, label_set: &S, reported: &AtomicBool, error: Error) -> io::Result<()> {
+        let invalid_series = match &self.invalid_series {
+            Some(invalid_series) => invalid_series,
+            None => return Err(error.into()),
+        };
+
+        invalid_series.errors.inc();
+
+        if !reported.swap(true, atomic::Ordering::Relaxed) {
+            (invalid_series.on_error)(label_set, &error);
+        }
+
+        Ok(())
+    }
+
+    /// Serializes the labels of a series into `buf`, returning `false` if
+    /// the series was dropped by relabeling.
+    fn serialize_label_set(&self, buf: &mut Vec<u8>, label_set: &S) -> Result<bool, Error> {
+        #[cfg(feature = "relabel")]
+        if let Some(relabeler) = &self.relabeler {
+            return match relabeler.relabel(self.pairs(label_set)?) {
+                Some(labels) => crate::relabel::write_labels(buf, &labels).map(|()| true),
+                None => Ok(false),
+            };
+        }
+
+        let const_labels = match &self.const_labels {
+            Some(const_labels) => const_labels,
+            None => return crate::to_writer(buf, label_set).map(|()| true),
+        };
+
+        buf.extend_from_slice(const_labels.serialized.as_bytes());
+
+        let len = buf.len();
+
+        if !const_labels.serialized.is_empty() {
+            buf.push(b',');
+        }
+
+        crate::top::write_unreserved(&mut *buf, label_set, |key| const_labels.contains_key(key))?;
+
+        if buf.len() == len + 1 {
+            // The label set was empty, remove the trailing comma.
+            buf.pop();
+        }
+
+        Ok(true)
+    }
+
+    /// Returns the set in which to record the labels of the encoded series,
+    /// if relabeling may give the same labels to several series.
+    fn encoded_series(&self) -> Option<HashSet<Vec<u8>>> {
+        #[cfg(feature = "relabel")]
+        if self.relabeler.is_some() {
+            return Some(HashSet::new());
+        }
+
+        None
+    }
+
+    /// Returns the constant labels followed by the labels of `label_set`.
+    #[cfg(any(feature = "protobuf", feature = "relabel"))]
+    fn pairs(&self, label_set: &S) -> Result<Vec<(Cow<'static, str>, String)>, Error> {
+        let mut pairs = match &self.const_labels {
+            Some(const_labels) => const_labels.pairs.clone(),
+            None => vec![],
+        };
+
+        for (name, value) in crate::top::pairs(label_set)? {
+            if let Some(const_labels) = &self.const_labels {
+                if const_labels.contains_key(&name) {
+                    return Err(crate::top::duplicate_key(&name));
+                }
+            }
+
+            pairs.push((name, value));
+        }
+
+        Ok(pairs)
+    }
+}
+
+/// Records the serialized labels of a kept series in `encoded`, returning
+/// an error if they were already recorded for another series.
+fn check_unique(
+    encoded: &mut Option<HashSet<Vec<u8>>>,
+    kept: bool,
+    serialized: &[u8],
+) -> Result<bool, Error> {
+    #[derive(Debug)]
+    struct DuplicateSeriesError(String);
+
+    impl std::error::Error for DuplicateSeriesError {
+        #[allow(deprecated)]
+        fn description(&self) -> &str {
+            "duplicate series"
+        }
+    }
+
+    impl fmt::Display for DuplicateSeriesError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "duplicate series ({{{}}})", self.0)
+        }
+    }
+
+    match encoded {
+        Some(encoded) if kept && encoded.contains(serialized) => Err(Error::invalid_input(
+            DuplicateSeriesError(String::from_utf8_lossy(serialized).into_owned()),
+        )),
+        Some(encoded) if kept => {
+            encoded.insert(serialized.to_vec());
+
+            Ok(true)
+        }
+        _ => Ok(kept),
+    }
+}
+
+#[derive(Debug)]
+struct ConstLabels {
+    serialized: String,
+    keys: HashSet<Cow<'static, str>>,
+    #[cfg(any(feature = "protobuf", feature = "relabel"))]
+    pairs: Vec<(Cow<'static, str>, String)>,
+}
+
+impl ConstLabels {
+    fn contains_key(&self, key: &str) -> bool {
+        self.keys.contains(key)
+    }
+}
+
+struct InvalidSeries<S> {
+    errors: Counter,
+    on_error: OnError<S>,
+}
+
+type OnError<S> = Arc<dyn Fn(&S, &Error) + Send + Sync>;
+
+/// The order in which the series of a family are encoded.
+enum Order<S> {
+    Unspecified,
+    Labels,
+    By(Compare<S>),
+}
+
+type Compare<S> = Arc<dyn Fn(&S, &S) -> Ordering + Send + Sync>;
+
+impl<S> Clone for Order<S> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Unspecified => Self::Unspecified,
+            Self::Labels => Self::Labels,
+            Self::By(compare) => Self::By(compare.clone()),
+        }
+    }
+}
+
+impl<S> fmt::Debug for Order<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Unspecified => "Unspecified",
+            Self::Labels => "Labels",
+            Self::By(_) => "By(..)",
+        })
+    }
+}
+
+impl<S> Clone for InvalidSeries<S> {
+    fn clone(&self) -> Self {
+        Self {
+            errors: self.errors.clone(),
+            on_error: self.on_error.clone(),
+        }
+    }
+}
+
+impl<S> fmt::Debug for InvalidSeries<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InvalidSeries")
+            .field("errors", &self.errors)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Clone, Eq, Hash, PartialEq)]
+#[repr(transparent)]
+struct Bridge<S>(S);
+
+impl<S> Bridge<S> {
+    fn from_ref(label_set: &S) -> &Self {
+        // SAFETY: `Self` is a transparent newtype wrapper.
+        unsafe { &*(label_set as *const S as *const Bridge<S>) }
+    }
+}
+
+impl<S> Encode for Bridge<S>
+where
+    S: Serialize,
+{
+    fn encode(&self, writer: &mut dyn io::Write) -> Result<(), std::io::Error> {
+        crate::to_writer(writer, &self.0)?;
+
+        Ok(())
+    }
+}
+
+impl<S> fmt::Debug for Bridge<S>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// A label set which was already serialized.
+struct Serialized<'a>(&'a [u8]);
+
+impl Encode for Serialized<'_> {
+    fn encode(&self, writer: &mut dyn io::Write) -> Result<(), std::io::Error> {
+        writer.write_all(self.0)
+    }
+}
@@ -0,0 +1,453 @@
+//! Encoding of metrics into the messages of the Prometheus protobuf
+//! exposition format, as defined in `io.prometheus.client`.
+//!
+//! Label sets are serialized with [`Serialize`] into [`LabelPair`] messages,
+//! without going through the text writer.
+//!
+//! #### Examples
+//!
+//! ```rust
+//! # use prometheus_client::metrics::counter::Counter;
+//! # use prost::Message;
+//! # use serde::Serialize;
+//! # use serde_prometheus_labels::{protobuf, Family};
+//! #
+//! #[derive(Clone, Eq, Hash, PartialEq, Serialize)]
+//! struct Labels {
+//!     host: String,
+//! }
+//!
+//! let family = <Family<Labels, Counter>>::default();
+//!
+//! family
+//!     .get_or_create(&Labels {
+//!         host: "unionize.org".to_string(),
+//!     })
+//!     .inc();
+//!
+//! let metric_family = protobuf::encode("requests", "Number of requests", &family).unwrap();
+//!
+//! assert_eq!(metric_family.name(), "requests_total");
+//! assert_eq!(metric_family.r#type(), protobuf::MetricType::Counter);
+//! assert_eq!(metric_family.metric[0].label[0].name(), "host");
+//! assert_eq!(metric_family.metric[0].label[0].value(), "unionize.org");
+//! assert_eq!(metric_family.metric[0].counter.as_ref().unwrap().value(), 1.0);
+//!
+//! // The exposition format is a stream of length-delimited messages.
+//! let serialized = metric_family.encode_length_delimited_to_vec();
+//! ```
+
+use super::{check_unique, Options, Order};
+use crate::Error;
+use prometheus_client::metrics::{self, counter, gauge, TypedMetric};
+use serde::Serialize;
+use std::sync::atomic::AtomicBool;
+#[cfg(not(any(target_arch = "mips", target_arch = "powerpc")))]
+use std::sync::atomic::AtomicU64;
+
+/// Encodes `metric` as a metric family named `name`.
+///
+/// Like in the text encoding of prometheus-client, the name of counters is
+/// suffixed with `_total`.
+pub fn encode<M>(name: &str, help: &str, metric: &M) -> Result<MetricFamily, Error>
+where
+    M: ?Sized + EncodeProtobuf,
+{
+    let (r#type, suffix) = match M::TYPE {
+        metrics::MetricType::Counter => (MetricType::Counter, "_total"),
+        metrics::MetricType::Gauge => (MetricType::Gauge, ""),
+        metrics::MetricType::Histogram => (MetricType::Histogram, ""),
+        metrics::MetricType::Info | metrics::MetricType::Unknown => (MetricType::Untyped, ""),
+    };
+
+    let mut metrics = vec![];
+    metric.encode_protobuf(&[], &mut metrics)?;
+
+    Ok(MetricFamily {
+        name: Some(format!("{name}{suffix}")),
+        help: Some(help.to_owned()),
+        r#type: Some(r#type.into()),
+        metric: metrics,
+        unit: None,
+    })
+}
+
+/// A metric which can be encoded with [`encode`].
+///
+/// It is implemented for the counters and gauges of prometheus-client, and
+/// for the [`Histogram`](crate::Histogram), [`Info`](crate::Info),
+/// [`CounterWithExemplar`](crate::CounterWithExemplar) and families of this
+/// crate.
+///
+/// Exemplars aren't encoded, as prometheus-client doesn't expose them, and
+/// neither are the histograms of prometheus-client and
+/// [`HistogramWithExemplars`](crate::HistogramWithExemplars), whose buckets
+/// it doesn't expose. The type of a [`Collector`](crate::Collector) or of a
+/// [`GroupMember`](crate::GroupMember) is only known at runtime, so they
+/// can't be encoded either.
+pub trait EncodeProtobuf: TypedMetric {
+    /// Pushes the series of this metric to `metrics`, with `labels` before
+    /// their own labels.
+    fn encode_protobuf(&self, labels: &[LabelPair], metrics: &mut Vec<Metric>)
+        -> Result<(), Error>;
+}
+
+macro_rules! counters {
+    ($($n:ty: $a:ty),+ $(,)?) => {$(
+        impl EncodeProtobuf for counter::Counter<$n, $a> {
+            fn encode_protobuf(
+                &self,
+                labels: &[LabelPair],
+                metrics: &mut Vec<Metric>,
+            ) -> Result<(), Error> {
+                push_counter(labels, metrics, self.get() as f64);
+
+                Ok(())
+            }
+        }
+
+        impl<E> EncodeProtobuf for super::CounterWithExemplar<E, $n, $a>
+        where
+            E: Serialize,
+        {
+            fn encode_protobuf(
+                &self,
+                labels: &[LabelPair],
+                metrics: &mut Vec<Metric>,
+            ) -> Result<(), Error> {
+                push_counter(labels, metrics, self.get() as f64);
+
+                Ok(())
+            }
+        }
+    )+}
+}
+
+fn push_counter(labels: &[LabelPair], metrics: &mut Vec<Metric>, value: f64) {
+    metrics.push(Metric {
+        label: labels.to_vec(),
+        counter: Some(Counter {
+            value: Some(value),
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+}
+
+macro_rules! gauges {
+    ($($n:ty: $a:ty),+ $(,)?) => {$(
+        impl EncodeProtobuf for gauge::Gauge<$n, $a> {
+            fn encode_protobuf(
+                &self,
+                labels: &[LabelPair],
+                metrics: &mut Vec<Metric>,
+            ) -> Result<(), Error> {
+                metrics.push(Metric {
+                    label: labels.to_vec(),
+                    gauge: Some(Gauge {
+                        value: Some(self.get() as f64),
+                    }),
+                    ..Default::default()
+                });
+
+                Ok(())
+            }
+        }
+    )+}
+}
+
+#[cfg(not(any(target_arch = "mips", target_arch = "powerpc")))]
+counters!(u64: AtomicU64, f64: AtomicU64);
+counters!(u32: std::sync::atomic::AtomicU32);
+
+#[cfg(not(any(target_arch = "mips", target_arch = "powerpc")))]
+gauges!(u64: AtomicU64, f64: AtomicU64);
+gauges!(u32: std::sync::atomic::AtomicU32);
+
+impl<S> Options<S>
+where
+    S: Serialize,
+{
+    pub(super) fn encode_protobuf<'a, M>(
+        &self,
+        labels: &[LabelPair],
+        metrics: &mut Vec<Metric>,
+        series: impl IntoIterator<Item = (&'a S, &'a M, &'a AtomicBool)>,
+    ) -> Result<(), Error>
+    where
+        S: 'a,
+        M: 'a + ?Sized + EncodeProtobuf,
+    {
+        let mut series = series.into_iter().collect::<Vec<_>>();
+
+        match &self.order {
+            Order::Unspecified => {}
+            Order::Labels => series.sort_by_cached_key(|(label_set, ..)| {
+                let mut buf = vec![];
+
+                self.serialize_label_set(&mut buf, label_set)
+                    .ok()
+                    .and_then(|kept| kept.then_some(buf))
+            }),
+            Order::By(compare) => series.sort_by(|(a, ..), (b, ..)| compare(a, b)),
+        }
+
+        let mut encoded = self.encoded_series();
+
+        for (label_set, metric, reported) in series {
+            let pairs = self.label_pairs(labels, label_set).and_then(|pairs| {
+                if encoded.is_some() && pairs.is_some() {
+                    let mut buf = vec![];
+
+                    self.serialize_label_set(&mut buf, label_set)?;
+                    check_unique(&mut encoded, true, &buf)?;
+                }
+
+                Ok(pairs)
+            });
+
+            match pairs {
+                Ok(Some(pairs)) => metric.encode_protobuf(&pairs, metrics)?,
+                Ok(None) => {}
+                Err(error) => self
+                    .invalid_series(label_set, reported, error)
+                    .map_err(Error::new)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns `labels` followed by the labels of a series, or `None` if
+    /// the series was dropped by relabeling.
+    fn label_pairs(
+        &self,
+        labels: &[LabelPair],
+        label_set: &S,
+    ) -> Result<Option<Vec<LabelPair>>, Error> {
+        let mut pairs = labels.to_vec();
+        let own_pairs = self.pairs(label_set)?;
+
+        #[cfg(feature = "relabel")]
+        let own_pairs = match &self.relabeler {
+            Some(relabeler) => match relabeler.relabel(own_pairs) {
+                Some(relabeled) => relabeled,
+                None => return Ok(None),
+            },
+            None => own_pairs,
+        };
+
+        pairs.extend(
+            own_pairs
+                .into_iter()
+                .map(|(name, value)| LabelPair::new(&name, value)),
+        );
+
+        Ok(Some(pairs))
+    }
+}
+
+/// Appends the labels of `label_set` to `labels`.
+pub(super) fn push_label_pairs(
+    labels: &mut Vec<LabelPair>,
+    label_set: &impl Serialize,
+) -> Result<(), Error> {
+    labels.extend(
+        crate::top::pairs(label_set)?
+            .into_iter()
+            .map(|(name, value)| LabelPair::new(&name, value)),
+    );
+
+    Ok(())
+}
+
+/// A label of a [`Metric`].
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct LabelPair {
+    #[prost(string, optional, tag = "1")]
+    pub name: Option<String>,
+    #[prost(string, optional, tag = "2")]
+    pub value: Option<String>,
+}
+
+impl LabelPair {
+    fn new(name: &str, value: String) -> Self {
+        Self {
+            name: Some(name.to_owned()),
+            value: Some(value),
+        }
+    }
+}
+
+/// The value of a gauge.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Gauge {
+    #[prost(double, optional, tag = "1")]
+    pub value: Option<f64>,
+}
+
+/// The value of a counter.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Counter {
+    #[prost(double, optional, tag = "1")]
+    pub value: Option<f64>,
+    #[prost(message, optional, tag = "2")]
+    pub exemplar: Option<Exemplar>,
+    #[prost(message, optional, tag = "3")]
+    pub created_timestamp: Option<Timestamp>,
+}
+
+/// A quantile of a [`Summary`].
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Quantile {
+    #[prost(double, optional, tag = "1")]
+    pub quantile: Option<f64>,
+    #[prost(double, optional, tag = "2")]
+    pub value: Option<f64>,
+}
+
+/// The value of a summary.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Summary {
+    #[prost(uint64, optional, tag = "1")]
+    pub sample_count: Option<u64>,
+    #[prost(double, optional, tag = "2")]
+    pub sample_sum: Option<f64>,
+    #[prost(message, repeated, tag = "3")]
+    pub quantile: Vec<Quantile>,
+    #[prost(message, optional, tag = "4")]
+    pub created_timestamp: Option<Timestamp>,
+}
+
+/// The value of an untyped metric.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Untyped {
+    #[prost(double, optional, tag = "1")]
+    pub value: Option<f64>,
+}
+
+/// The value of a histogram, with either classic buckets or native
+/// sparse buckets.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Histogram {
+    #[prost(uint64, optional, tag = "1")]
+    pub sample_count: Option<u64>,
+    #[prost(double, optional, tag = "4")]
+    pub sample_count_float: Option<f64>,
+    #[prost(double, optional, tag = "2")]
+    pub sample_sum: Option<f64>,
+    #[prost(message, repeated, tag = "3")]
+    pub bucket: Vec<Bucket>,
+    #[prost(message, optional, tag = "15")]
+    pub created_timestamp: Option<Timestamp>,
+    #[prost(sint32, optional, tag = "5")]
+    pub schema: Option<i32>,
+    #[prost(double, optional, tag = "6")]
+    pub zero_threshold: Option<f64>,
+    #[prost(uint64, optional, tag = "7")]
+    pub zero_count: Option<u64>,
+    #[prost(double, optional, tag = "8")]
+    pub zero_count_float: Option<f64>,
+    #[prost(message, repeated, tag = "9")]
+    pub negative_span: Vec<BucketSpan>,
+    #[prost(sint64, repeated, packed = "false", tag = "10")]
+    pub negative_delta: Vec<i64>,
+    #[prost(double, repeated, packed = "false", tag = "11")]
+    pub negative_count: Vec<f64>,
+    #[prost(message, repeated, tag = "12")]
+    pub positive_span: Vec<BucketSpan>,
+    #[prost(sint64, repeated, packed = "false", tag = "13")]
+    pub positive_delta: Vec<i64>,
+    #[prost(double, repeated, packed = "false", tag = "14")]
+    pub positive_count: Vec<f64>,
+    #[prost(message, repeated, tag = "16")]
+    pub exemplars: Vec<Exemplar>,
+}
+
+/// A classic bucket of a [`Histogram`].
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Bucket {
+    #[prost(uint64, optional, tag = "1")]
+    pub cumulative_count: Option<u64>,
+    #[prost(double, optional, tag = "4")]
+    pub cumulative_count_float: Option<f64>,
+    #[prost(double, optional, tag = "2")]
+    pub upper_bound: Option<f64>,
+    #[prost(message, optional, tag = "3")]
+    pub exemplar: Option<Exemplar>,
+}
+
+/// A span of consecutive native buckets of a [`Histogram`].
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BucketSpan {
+    #[prost(sint32, optional, tag = "1")]
+    pub offset: Option<i32>,
+    #[prost(uint32, optional, tag = "2")]
+    pub length: Option<u32>,
+}
+
+/// An exemplar of a [`Counter`] or of a [`Histogram`].
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Exemplar {
+    #[prost(message, repeated, tag = "1")]
+    pub label: Vec<LabelPair>,
+    #[prost(double, optional, tag = "2")]
+    pub value: Option<f64>,
+    #[prost(message, optional, tag = "3")]
+    pub timestamp: Option<Timestamp>,
+}
+
+/// A point in time, encoded like `google.protobuf.Timestamp`.
+#[derive(Clone, Copy, PartialEq, prost::Message)]
+pub struct Timestamp {
+    #[prost(int64, tag = "1")]
+    pub seconds: i64,
+    #[prost(int32, tag = "2")]
+    pub nanos: i32,
+}
+
+/// A series of a [`MetricFamily`].
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Metric {
+    #[prost(message, repeated, tag = "1")]
+    pub label: Vec<LabelPair>,
+    #[prost(message, optional, tag = "2")]
+    pub gauge: Option<Gauge>,
+    #[prost(message, optional, tag = "3")]
+    pub counter: Option<Counter>,
+    #[prost(message, optional, tag = "4")]
+    pub summary: Option<Summary>,
+    #[prost(message, optional, tag = "5")]
+    pub untyped: Option<Untyped>,
+    #[prost(message, optional, tag = "7")]
+    pub histogram: Option<Histogram>,
+    #[prost(int64, optional, tag = "6")]
+    pub timestamp_ms: Option<i64>,
+}
+
+/// A metric family, as returned by [`encode`].
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct MetricFamily {
+    #[prost(string, optional, tag = "1")]
+    pub name: Option<String>,
+    #[prost(string, optional, tag = "2")]
+    pub help: Option<String>,
+    #[prost(enumeration = "MetricType", optional, tag = "3")]
+    pub r#type: Option<i32>,
+    #[prost(message, repeated, tag = "4")]
+    pub metric: Vec<Metric>,
+    #[prost(string, optional, tag = "5")]
+    pub unit: Option<String>,
+}
+
+/// The type of a [`MetricFamily`].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, prost::Enumeration)]
+#[repr(i32)]
+pub enum MetricType {
+    Counter = 0,
+    Gauge = 1,
+    Summary = 2,
+    Untyped = 3,
+    Histogram = 4,
+    GaugeHistogram = 5,
+}
@@ -0,0 +1,258 @@
+use super::{LabelSetRef, Options, Shard};
+use crate::Error;
+use parking_lot::MappedRwLockReadGuard;
+use prometheus_client::{
+    encoding::text::{EncodeMetric, Encoder},
+    metrics::{counter::Counter, family::MetricConstructor, MetricType, TypedMetric},
+};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::io;
+use std::sync::Arc;
+
+/// A [`Family`](super::Family) whose series are partitioned by the hash of
+/// their label set across several locks, to reduce contention when series
+/// are created concurrently.
+///
+/// It is still encoded as a single metric family.
+///
+/// #### Examples
+///
+/// ```rust
+/// # use prometheus_client::{
+/// #     encoding::text::encode,
+/// #     metrics::counter::Counter,
+/// #     registry::Registry,
+/// # };
+/// # use serde::Serialize;
+/// # use serde_prometheus_labels::ShardedFamily;
+/// #
+/// #[derive(Clone, Eq, Hash, PartialEq, Serialize)]
+/// struct Labels {
+///     host: String,
+/// }
+///
+/// let family = <ShardedFamily<Labels, Counter>>::new(64);
+/// let mut registry = Registry::default();
+///
+/// registry.register("requests", "Number of requests", family.clone());
+///
+/// family
+///     .get_or_create(&Labels {
+///         host: "unionize.org".to_string(),
+///     })
+///     .inc();
+///
+/// let mut serialized = vec![];
+/// encode(&mut serialized, &registry).unwrap();
+///
+/// assert_eq!(
+///     String::from_utf8(serialized).unwrap(),
+///     concat!(
+///         "# HELP requests Number of requests.\n",
+///         "# TYPE requests counter\n",
+///         "requests_total{host=\"unionize.org\"} 1\n",
+///         "# EOF\n",
+///     ),
+/// );
+/// ```
+#[derive(Debug)]
+pub struct ShardedFamily<S, M, C = fn() -> M> {
+    hasher: RandomState,
+    shards: Arc<[Shard<S, M>]>,
+    constructor: C,
+    options: Options<S>,
+}
+
+impl<S, M> ShardedFamily<S, M>
+where
+    S: Clone + Eq + Hash,
+    M: Default,
+{
+    /// Creates a family with `shards` shards.
+    ///
+    /// Panics if `shards` is zero.
+    pub fn new(shards: usize) -> Self {
+        Self::new_with_constructor(shards, M::default)
+    }
+}
+
+impl<S, M, C> ShardedFamily<S, M, C>
+where
+    S: Clone + Eq + Hash,
+{
+    /// Creates a family with `shards` shards, whose metrics are created
+    /// with `constructor`.
+    ///
+    /// Panics if `shards` is zero.
+    pub fn new_with_constructor(shards: usize, constructor: C) -> Self {
+        assert!(shards > 0, "a sharded family needs at least one shard");
+
+        Self {
+            hasher: RandomState::new(),
+            shards: (0..shards).map(|_| Shard::default()).collect(),
+            constructor,
+            options: Default::default(),
+        }
+    }
+
+    /// See [`Family::with_const_labels`](super::Family::with_const_labels).
+    pub fn with_const_labels(mut self, const_labels: &impl Serialize) -> Result<Self, Error> {
+        self.options.set_const_labels(const_labels)?;
+
+        Ok(self)
+    }
+
+    /// See [`Family::with_relabeler`](super::Family::with_relabeler).
+    #[cfg(feature = "relabel")]
+    pub fn with_relabeler(mut self, relabeler: crate::relabel::Relabeler) -> Self {
+        self.options.set_relabeler(relabeler);
+
+        self
+    }
+
+    /// See
+    /// [`Family::skip_invalid_series`](super::Family::skip_invalid_series).
+    pub fn skip_invalid_series(
+        mut self,
+        errors: Counter,
+        on_error: impl Fn(&S, &Error) + Send + Sync + 'static,
+    ) -> Self {
+        self.options.skip_invalid_series(errors, on_error);
+
+        self
+    }
+
+    /// See [`Family::sort_by_labels`](super::Family::sort_by_labels).
+    pub fn sort_by_labels(mut self) -> Self {
+        self.options.sort_by_labels();
+
+        self
+    }
+
+    /// See [`Family::sort_by`](super::Family::sort_by).
+    pub fn sort_by(mut self, compare: impl Fn(&S, &S) -> Ordering + Send + Sync + 'static) -> Self {
+        self.options.sort_by(compare);
+
+        self
+    }
+
+    fn shard<Q>(&self, label_set: &Q) -> &Shard<S, M>
+    where
+        Q: ?Sized + LabelSetRef<S>,
+    {
+        let hash = self.hasher.hash_one(label_set);
+
+        &self.shards[(hash % self.shards.len() as u64) as usize]
+    }
+}
+
+impl<S, M, C> ShardedFamily<S, M, C>
+where
+    S: Clone + Eq + Hash,
+    C: MetricConstructor<M>,
+{
+    /// See [`Family::get_or_create`](super::Family::get_or_create).
+    pub fn get_or_create<Q>(&self, label_set: &Q) -> MappedRwLockReadGuard<'_, M>
+    where
+        Q: ?Sized + LabelSetRef<S>,
+    {
+        self.shard(label_set)
+            .get_or_create(label_set, &self.constructor)
+    }
+
+    /// See
+    /// [`Family::get_or_create_owned`](super::Family::get_or_create_owned).
+    pub fn get_or_create_owned<Q>(&self, label_set: &Q) -> Arc<M>
+    where
+        Q: ?Sized + LabelSetRef<S>,
+    {
+        self.shard(label_set)
+            .get_or_create_owned(label_set, &self.constructor)
+    }
+}
+
+impl<S, M, C> EncodeMetric for ShardedFamily<S, M, C>
+where
+    S: Clone + Eq + Hash + Serialize,
+    M: EncodeMetric + TypedMetric,
+    C: MetricConstructor<M>,
+{
+    fn encode(&self, mut encoder: Encoder) -> io::Result<()> {
+        if self.options.is_ordered() {
+            // All shards must be locked at once to sort all their series.
+            let shards = self.shards.iter().map(Shard::read).collect::<Vec<_>>();
+
+            return self.options.encode(
+                &mut encoder,
+                shards
+                    .iter()
+                    .flat_map(|metrics| metrics.iter())
+                    .map(|(label_set, series)| (label_set, &*series.metric, &series.reported)),
+            );
+        }
+
+        for shard in self.shards.iter() {
+            self.options.encode(
+                &mut encoder,
+                shard
+                    .read()
+                    .iter()
+                    .map(|(label_set, series)| (label_set, &*series.metric, &series.reported)),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn metric_type(&self) -> MetricType {
+        M::TYPE
+    }
+}
+
+impl<S, M, C> TypedMetric for ShardedFamily<S, M, C>
+where
+    M: TypedMetric,
+{
+    const TYPE: MetricType = <M as TypedMetric>::TYPE;
+}
+
+impl<S, M, C> Clone for ShardedFamily<S, M, C>
+where
+    C: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            hasher: self.hasher.clone(),
+            shards: self.shards.clone(),
+            constructor: self.constructor.clone(),
+            options: self.options.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "protobuf")]
+impl<S, M, C> super::protobuf::EncodeProtobuf for ShardedFamily<S, M, C>
+where
+    S: Clone + Eq + Hash + Serialize,
+    M: super::protobuf::EncodeProtobuf,
+{
+    fn encode_protobuf(
+        &self,
+        labels: &[super::protobuf::LabelPair],
+        metrics: &mut Vec<super::protobuf::Metric>,
+    ) -> Result<(), Error> {
+        let shards = self.shards.iter().map(Shard::read).collect::<Vec<_>>();
+
+        self.options.encode_protobuf(
+            labels,
+            metrics,
+            shards
+                .iter()
+                .flat_map(|metrics| metrics.iter())
+                .map(|(label_set, series)| (label_set, &*series.metric, &series.reported)),
+        )
+    }
+}
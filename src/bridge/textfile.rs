@@ -0,0 +1,469 @@
+use crate::{de, Error};
+use prometheus_client::{
+    encoding::text::{self, EncodeMetric, Encoder},
+    metrics::MetricType,
+    registry::Registry,
+};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Write as _;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{error, fmt, process, str};
+
+/// Writes `.prom` files for the textfile collector of node_exporter.
+///
+/// Files are written to a temporary file in the same directory, which is
+/// then atomically renamed into place, so that node_exporter never reads a
+/// partially written file.
+///
+/// node_exporter only parses the Prometheus text format 0.0.4, so the
+/// OpenMetrics exposition of prometheus-client is converted to it: counter
+/// families are named after their `_total` samples, info metrics become
+/// gauges named after their `_info` samples, unknown metrics become
+/// untyped, and `# EOF`, `_created` samples and exemplars are dropped.
+///
+/// Before a file is written, its metric families are checked against the
+/// ones of the other `.prom` files of the directory: a family found in
+/// several files must have the same help, type and label names in all of
+/// them, and no series may be written in more than one file.
+///
+/// #### Examples
+///
+/// ```rust
+/// # use prometheus_client::metrics::gauge::Gauge;
+/// # use serde::Serialize;
+/// # use serde_prometheus_labels::{Family, TextfileWriter};
+/// #
+/// #[derive(Clone, Eq, Hash, PartialEq, Serialize)]
+/// struct Labels {
+///     job: &'static str,
+/// }
+///
+/// let last_success = <Family<Labels, Gauge>>::default();
+///
+/// last_success
+///     .get_or_create(&Labels { job: "backup" })
+///     .set(1_666_000_000);
+///
+/// let dir = std::env::temp_dir().join(format!("textfile-doc-{}", std::process::id()));
+/// std::fs::create_dir_all(&dir).unwrap();
+///
+/// TextfileWriter::new(&dir)
+///     .write_metrics(
+///         "backup.prom",
+///         [(
+///             "cron_last_success_timestamp_seconds",
+///             "Last time the job succeeded",
+///             &last_success as _,
+///         )],
+///     )
+///     .unwrap();
+///
+/// assert_eq!(
+///     std::fs::read_to_string(dir.join("backup.prom")).unwrap(),
+///     concat!(
+///         "# HELP cron_last_success_timestamp_seconds Last time the job succeeded.\n",
+///         "# TYPE cron_last_success_timestamp_seconds gauge\n",
+///         "cron_last_success_timestamp_seconds{job=\"backup\"} 1666000000\n",
+///     ),
+/// );
+/// #
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct TextfileWriter {
+    dir: PathBuf,
+}
+
+impl TextfileWriter {
+    /// Creates a writer of files in the directory `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Writes the metrics of `registry` to the file `file_name`, which
+    /// must end with `.prom`.
+    pub fn write<M>(&self, file_name: &str, registry: &Registry<M>) -> Result<(), Error>
+    where
+        M: EncodeMetric,
+    {
+        check_file_name(file_name)?;
+
+        let mut exposition = vec![];
+        text::encode(&mut exposition, registry).map_err(Error::new)?;
+
+        self.write_exposition(file_name, &exposition)
+    }
+
+    /// Writes the given metrics, such as families, to the file `file_name`,
+    /// as if they were registered with their name and help in an empty
+    /// registry.
+    pub fn write_metrics<'a>(
+        &self,
+        file_name: &str,
+        metrics: impl IntoIterator<Item = (&'a str, &'a str, &'a dyn EncodeMetric)>,
+    ) -> Result<(), Error> {
+        struct Borrowed<'a>(&'a dyn EncodeMetric);
+
+        impl EncodeMetric for Borrowed<'_> {
+            fn encode(&self, encoder: Encoder) -> io::Result<()> {
+                self.0.encode(encoder)
+            }
+
+            fn metric_type(&self) -> MetricType {
+                self.0.metric_type()
+            }
+        }
+
+        let mut registry = Registry::default();
+
+        for (name, help, metric) in metrics {
+            registry.register(name, help, Borrowed(metric));
+        }
+
+        self.write(file_name, &registry)
+    }
+
+    fn write_exposition(&self, file_name: &str, exposition: &[u8]) -> Result<(), Error> {
+        static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+        let path = self.dir.join(file_name);
+        let exposition = str::from_utf8(exposition)
+            .map_err(|error| Error::new(io::Error::new(io::ErrorKind::InvalidData, error)))
+            .and_then(|exposition| to_prometheus_text(&path, exposition))?;
+        let families = Families::parse(&path, &exposition)?;
+
+        for entry in fs::read_dir(&self.dir).map_err(Error::new)? {
+            let other_path = entry.map_err(Error::new)?.path();
+
+            if other_path == path || other_path.extension().map_or(true, |ext| ext != "prom") {
+                continue;
+            }
+
+            let other_exposition = fs::read_to_string(&other_path).map_err(Error::new)?;
+            families.check(&Families::parse(&other_path, &other_exposition)?)?;
+        }
+
+        // The name is unique among the writers of this process, and a stale
+        // file left by another process with the same pid is truncated.
+        let temp_path = self.dir.join(format!(
+            ".{file_name}.{}.{}.tmp",
+            process::id(),
+            TEMP_FILES.fetch_add(1, Ordering::Relaxed),
+        ));
+
+        let result = (|| {
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&temp_path)?;
+
+            file.write_all(exposition.as_bytes())?;
+            file.sync_all()?;
+
+            fs::rename(&temp_path, &path)
+        })();
+
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+
+        result.map_err(Error::new)?;
+
+        // Persists the rename itself.
+        #[cfg(unix)]
+        File::open(&self.dir)
+            .and_then(|dir| dir.sync_all())
+            .map_err(Error::new)?;
+
+        Ok(())
+    }
+}
+
+/// Converts the OpenMetrics exposition of prometheus-client to the
+/// Prometheus text format 0.0.4.
+fn to_prometheus_text(path: &Path, open_metrics: &str) -> Result<String, Error> {
+    let types = open_metrics
+        .lines()
+        .filter_map(|line| line.strip_prefix("# TYPE ")?.split_once(' '))
+        .collect::<HashMap<_, _>>();
+
+    let mut text = String::with_capacity(open_metrics.len());
+
+    for line in open_metrics.lines() {
+        if let Some(comment) = line.strip_prefix('#') {
+            let mut words = comment.trim_start().splitn(3, ' ');
+
+            match (words.next(), words.next(), words.next()) {
+                (Some(keyword @ ("HELP" | "TYPE")), Some(name), rest) => {
+                    let (suffix, r#type) = match types.get(name).copied() {
+                        Some("counter") => ("_total", "counter"),
+                        Some("info") => ("_info", "gauge"),
+                        Some(r#type @ ("gauge" | "histogram" | "summary")) => ("", r#type),
+                        _ => ("", "untyped"),
+                    };
+
+                    let rest = match keyword {
+                        "HELP" => rest.unwrap_or(""),
+                        _ => r#type,
+                    };
+
+                    let _ = match rest {
+                        "" => writeln!(text, "# {keyword} {name}{suffix}"),
+                        _ => writeln!(text, "# {keyword} {name}{suffix} {rest}"),
+                    };
+                }
+                (Some("EOF" | "UNIT"), ..) => {}
+                _ => {
+                    text.push_str(line);
+                    text.push('\n');
+                }
+            }
+
+            continue;
+        }
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (name, _, rest) = parse_sample(path, line)?;
+
+        if name
+            .strip_suffix("_created")
+            .is_some_and(|family| types.get(family) == Some(&"counter"))
+        {
+            continue;
+        }
+
+        // Exemplars are dropped.
+        let value = rest.split_once(" # ").map_or(rest, |(value, _)| value);
+
+        text.push_str(&line[..line.len() - rest.len()]);
+        text.push_str(value);
+        text.push('\n');
+    }
+
+    Ok(text)
+}
+
+/// The metric families of a `.prom` file.
+struct Families<'a> {
+    path: &'a Path,
+    families: HashMap<&'a str, FamilyDesc<'a>>,
+}
+
+#[derive(Default)]
+struct FamilyDesc<'a> {
+    help: Option<&'a str>,
+    r#type: Option<&'a str>,
+    label_names: BTreeSet<BTreeSet<&'a str>>,
+    series: HashSet<(&'a str, de::Pairs<'a>)>,
+}
+
+impl<'a> Families<'a> {
+    fn parse(path: &'a Path, exposition: &'a str) -> Result<Self, Error> {
+        let mut families = HashMap::<_, FamilyDesc>::new();
+        let mut current = None;
+
+        for line in exposition.lines().filter(|line| !line.trim().is_empty()) {
+            if let Some(comment) = line.strip_prefix('#') {
+                let mut words = comment.trim_start().splitn(3, ' ');
+
+                match (words.next(), words.next(), words.next()) {
+                    (Some("HELP"), Some(name), help) => {
+                        families.entry(name).or_default().help = Some(help.unwrap_or(""));
+                    }
+                    (Some("TYPE"), Some(name), Some(r#type)) => {
+                        families.entry(name).or_default().r#type = Some(r#type);
+                        current = Some(name);
+                    }
+                    _ => {}
+                }
+
+                continue;
+            }
+
+            let (name, mut labels, _) = parse_sample(path, line)?;
+
+            let family = current
+                .filter(|family| is_family_sample(family, name))
+                .unwrap_or(name);
+
+            labels.sort();
+
+            let desc = families.entry(family).or_default();
+
+            desc.label_names.insert(
+                labels
+                    .iter()
+                    .map(|(key, _)| *key)
+                    .filter(|key| *key != "le" && *key != "quantile")
+                    .collect(),
+            );
+            desc.series.insert((name, labels));
+        }
+
+        Ok(Self { path, families })
+    }
+
+    /// Checks that the families in `self` are consistent with `other`.
+    fn check(&self, other: &Families<'_>) -> Result<(), Error> {
+        for (name, desc) in &self.families {
+            let other_desc = match other.families.get(name) {
+                Some(other_desc) => other_desc,
+                None => continue,
+            };
+
+            let inconsistency = if desc.help != other_desc.help {
+                "inconsistent help"
+            } else if desc.r#type != other_desc.r#type {
+                "inconsistent type"
+            } else if desc.label_names != other_desc.label_names {
+                "inconsistent label names"
+            } else if !desc.series.is_disjoint(&other_desc.series) {
+                "duplicate series"
+            } else {
+                continue;
+            };
+
+            return Err(inconsistent(other.path, name, inconsistency));
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses the name and the labels of a sample line, and returns them with
+/// the rest of the line.
+fn parse_sample<'a>(
+    path: &Path,
+    line: &'a str,
+) -> Result<(&'a str, de::Pairs<'a>, &'a str), Error> {
+    let end = line
+        .find(['{', ' '])
+        .ok_or_else(|| invalid_line(path, line))?;
+    let (name, mut rest) = line.split_at(end);
+    let mut pairs = vec![];
+
+    if let Some(mut labels) = rest.strip_prefix('{') {
+        pairs = de::parse_pairs(&mut labels).map_err(|_| invalid_line(path, line))?;
+        rest = labels
+            .strip_prefix('}')
+            .ok_or_else(|| invalid_line(path, line))?;
+    }
+
+    let rest = rest
+        .strip_prefix(' ')
+        .ok_or_else(|| invalid_line(path, line))?;
+
+    Ok((name, pairs, rest))
+}
+
+/// Whether a sample named `name` belongs to the metric family `family`.
+fn is_family_sample(family: &str, name: &str) -> bool {
+    name.strip_prefix(family).is_some_and(|suffix| {
+        matches!(
+            suffix,
+            "" | "_total" | "_bucket" | "_sum" | "_count" | "_info" | "_created",
+        )
+    })
+}
+
+fn check_file_name(file_name: &str) -> Result<(), Error> {
+    if file_name.ends_with(".prom")
+        && !file_name.starts_with('.')
+        && !file_name.contains(std::path::is_separator)
+    {
+        return Ok(());
+    }
+
+    #[derive(Debug)]
+    struct InvalidFileNameError(String);
+
+    impl error::Error for InvalidFileNameError {
+        #[allow(deprecated)]
+        fn description(&self) -> &str {
+            "invalid textfile name"
+        }
+    }
+
+    impl fmt::Display for InvalidFileNameError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "invalid textfile name ({:?})", self.0)
+        }
+    }
+
+    Err(Error::invalid_input(InvalidFileNameError(
+        file_name.to_owned(),
+    )))
+}
+
+fn inconsistent(path: &Path, family: &str, inconsistency: &'static str) -> Error {
+    #[derive(Debug)]
+    struct InconsistentTextfileError {
+        path: PathBuf,
+        family: String,
+        inconsistency: &'static str,
+    }
+
+    impl error::Error for InconsistentTextfileError {
+        #[allow(deprecated)]
+        fn description(&self) -> &str {
+            "inconsistent textfile"
+        }
+    }
+
+    impl fmt::Display for InconsistentTextfileError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "{} for metric family {:?} in {}",
+                self.inconsistency,
+                self.family,
+                self.path.display(),
+            )
+        }
+    }
+
+    Error::new(io::Error::new(
+        io::ErrorKind::InvalidData,
+        InconsistentTextfileError {
+            path: path.to_owned(),
+            family: family.to_owned(),
+            inconsistency,
+        },
+    ))
+}
+
+fn invalid_line(path: &Path, line: &str) -> Error {
+    #[derive(Debug)]
+    struct InvalidLineError {
+        path: PathBuf,
+        line: String,
+    }
+
+    impl error::Error for InvalidLineError {
+        #[allow(deprecated)]
+        fn description(&self) -> &str {
+            "invalid line"
+        }
+    }
+
+    impl fmt::Display for InvalidLineError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "invalid line {:?} in {}", self.line, self.path.display())
+        }
+    }
+
+    Error::new(io::Error::new(
+        io::ErrorKind::InvalidData,
+        InvalidLineError {
+            path: path.to_owned(),
+            line: line.to_owned(),
+        },
+    ))
+}
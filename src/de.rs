@@ -0,0 +1,301 @@
+use crate::Error;
+use alloc::borrow::{Cow, ToOwned};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::{error, fmt};
+use serde::de::{
+    self, value::CowStrDeserializer, DeserializeSeed, Deserializer, IntoDeserializer, MapAccess,
+    Visitor,
+};
+use serde::forward_to_deserialize_any;
+
+/// The labels of a label set, with their unescaped values.
+pub(crate) type Pairs<'de> = Vec<(&'de str, Cow<'de, str>)>;
+
+/// Parses comma-separated key-value pairs from the start of `input`, up to
+/// its end or to the first `}` outside of a label value.
+///
+/// `input` then points to the rest of the string.
+pub(crate) fn parse_pairs<'de>(input: &mut &'de str) -> Result<Pairs<'de>, Error> {
+    let mut pairs = vec![];
+
+    while !input.is_empty() && !input.starts_with('}') {
+        let (key, rest) = input
+            .split_once("=\"")
+            .ok_or_else(|| syntax_error("expected `=\"` after key"))?;
+
+        let mut chars = key.chars();
+
+        if !chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == ':')
+            || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+        {
+            return Err(syntax_error("invalid key"));
+        }
+
+        let (value, rest) = parse_value(rest)?;
+        pairs.push((key, value));
+
+        *input = match rest.strip_prefix(',') {
+            Some(rest) => rest,
+            None if rest.is_empty() || rest.starts_with('}') => rest,
+            None => return Err(syntax_error("expected `,` after label value")),
+        };
+    }
+
+    Ok(pairs)
+}
+
+/// Parses a quoted label value, whose opening quote was already consumed.
+fn parse_value(input: &str) -> Result<(Cow<'_, str>, &str), Error> {
+    let end = input
+        .find(['"', '\\'])
+        .ok_or_else(|| syntax_error("unterminated label value"))?;
+
+    if input.as_bytes()[end] == b'"' {
+        return Ok((Cow::Borrowed(&input[..end]), &input[end + 1..]));
+    }
+
+    let mut value = input[..end].to_owned();
+    let mut chars = input[end..].char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((Cow::Owned(value), &input[end + i + 1..])),
+            '\\' => match chars.next() {
+                Some((_, 'n')) => value.push('\n'),
+                Some((_, c @ ('"' | '\\'))) => value.push(c),
+                _ => return Err(syntax_error("invalid escape sequence")),
+            },
+            c => value.push(c),
+        }
+    }
+
+    Err(syntax_error("unterminated label value"))
+}
+
+/// A deserializer of the labels of a label set, as a map or a struct.
+pub(crate) struct LabelsDeserializer<'de> {
+    pairs: vec::IntoIter<(&'de str, Cow<'de, str>)>,
+    value: Option<Cow<'de, str>>,
+}
+
+impl<'de> LabelsDeserializer<'de> {
+    pub(crate) fn new(pairs: Pairs<'de>) -> Self {
+        Self {
+            pairs: pairs.into_iter(),
+            value: None,
+        }
+    }
+}
+
+impl<'de> Deserializer<'de> for LabelsDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_map(self)
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.pairs.len() != 0 {
+            return Err(de::Error::invalid_length(self.pairs.len(), &"no labels"));
+        }
+
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit_struct seq tuple tuple_struct map struct
+        enum identifier ignored_any
+    }
+}
+
+impl<'de> MapAccess<'de> for LabelsDeserializer<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        let (key, value) = match self.pairs.next() {
+            Some(pair) => pair,
+            None => return Ok(None),
+        };
+
+        self.value = Some(value);
+
+        seed.deserialize(de::value::BorrowedStrDeserializer::new(key))
+            .map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| de::Error::custom("value is missing"))?;
+
+        seed.deserialize(ValueDeserializer(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.pairs.len())
+    }
+}
+
+/// A deserializer of a label value.
+///
+/// Empty values are deserialized as `None` and as `()`, as they are what
+/// the serializer writes for them.
+struct ValueDeserializer<'de>(Cow<'de, str>);
+
+macro_rules! parse {
+    ($($method:ident => $visit:ident,)*) => {$(
+        fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+        where
+            V: Visitor<'de>,
+        {
+            match self.0.parse() {
+                Ok(v) => visitor.$visit(v),
+                Err(_) => Err(de::Error::invalid_value(
+                    de::Unexpected::Str(&self.0),
+                    &visitor,
+                )),
+            }
+        }
+    )*}
+}
+
+impl<'de> Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Cow::Borrowed(value) => visitor.visit_borrowed_str(value),
+            Cow::Owned(value) => visitor.visit_string(value),
+        }
+    }
+
+    parse! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.0.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        if !self.0.is_empty() {
+            return Err(de::Error::invalid_value(
+                de::Unexpected::Str(&self.0),
+                &visitor,
+            ));
+        }
+
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let variant: CowStrDeserializer<'de, Error> = self.0.into_deserializer();
+
+        visitor.visit_enum(variant)
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf seq tuple tuple_struct map struct identifier
+        ignored_any
+    }
+}
+
+fn syntax_error(message: &'static str) -> Error {
+    #[derive(Debug)]
+    struct SyntaxError(&'static str);
+
+    impl error::Error for SyntaxError {
+        #[allow(deprecated)]
+        fn description(&self) -> &str {
+            "invalid label set"
+        }
+    }
+
+    impl fmt::Display for SyntaxError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "invalid label set ({})", self.0)
+        }
+    }
+
+    Error::invalid_input(SyntaxError(message))
+}
@@ -1,15 +1,30 @@
-use std::error;
-use std::fmt;
+use alloc::boxed::Box;
+use alloc::string::ToString;
+use core::error;
+use core::fmt;
+#[cfg(feature = "std")]
 use std::io;
-use std::str;
 
-/// A serialization error.
+/// A serialization or deserialization error.
 ///
-/// Can be converted to [`std::io::Error`].
+#[cfg_attr(
+    feature = "std",
+    doc = "Can be converted to [`std::io::Error`] when the feature \"std\" is enabled."
+)]
+#[cfg_attr(
+    not(feature = "std"),
+    doc = "Can be converted to `std::io::Error` when the feature \"std\" is enabled."
+)]
 pub struct Error {
-    inner: io::Error,
+    inner: Inner,
 }
 
+#[cfg(feature = "std")]
+type Inner = io::Error;
+
+#[cfg(not(feature = "std"))]
+type Inner = Box<dyn error::Error + Send + Sync>;
+
 impl fmt::Debug for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.inner.fmt(f)
@@ -22,6 +37,7 @@ impl fmt::Display for Error {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<Error> for io::Error {
     fn from(error: Error) -> Self {
         error.inner
@@ -46,18 +62,55 @@ impl error::Error for Error {
 
 impl serde::ser::Error for Error {
     fn custom<T: fmt::Display>(msg: T) -> Self {
-        Self::new(io::Error::new(io::ErrorKind::Other, msg.to_string()))
+        Self::other(msg.to_string())
+    }
+}
+
+impl serde::de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self::invalid_input(msg.to_string())
     }
 }
 
 impl Error {
+    #[cfg(feature = "std")]
     pub(crate) fn new(inner: io::Error) -> Self {
         Self { inner }
     }
 
+    #[cfg(feature = "std")]
     pub(crate) fn invalid_input(inner: impl Into<Box<dyn error::Error + Send + Sync>>) -> Self {
         Self::new(io::Error::new(io::ErrorKind::InvalidInput, inner))
     }
+
+    #[cfg(not(feature = "std"))]
+    pub(crate) fn invalid_input(inner: impl Into<Box<dyn error::Error + Send + Sync>>) -> Self {
+        Self {
+            inner: inner.into(),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn other(inner: impl Into<Box<dyn error::Error + Send + Sync>>) -> Self {
+        Self::new(io::Error::other(inner))
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub(crate) fn other(inner: impl Into<Box<dyn error::Error + Send + Sync>>) -> Self {
+        Self {
+            inner: inner.into(),
+        }
+    }
+
+    #[cfg(all(feature = "bytes", feature = "std"))]
+    pub(crate) fn write_zero() -> Self {
+        Self::new(io::ErrorKind::WriteZero.into())
+    }
+
+    #[cfg(all(feature = "bytes", not(feature = "std")))]
+    pub(crate) fn write_zero() -> Self {
+        Self::other("failed to write whole buffer")
+    }
 }
 
 #[derive(Debug)]
@@ -77,7 +130,7 @@ pub(crate) enum Unexpected {
 }
 
 impl fmt::Display for Unexpected {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Unexpected::Bool(bool) => fmt::Debug::fmt(bool, f),
             Unexpected::Map(None) => write!(f, "map"),
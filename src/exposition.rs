@@ -0,0 +1,625 @@
+//! Writing and parsing of complete exposition lines, for exporters which
+//! don't use a metrics library and for tools which scrape them.
+//!
+//! #### Examples
+//!
+//! Writing:
+//!
+//! ```rust
+//! # use serde::Serialize;
+//! # use serde_prometheus_labels::exposition::{MetricType, Writer};
+//! #
+//! #[derive(Serialize)]
+//! struct Labels {
+//!     method: &'static str,
+//!     path: &'static str,
+//! }
+//!
+//! let mut writer = Writer::open_metrics(vec![]);
+//!
+//! writer
+//!     .help("http_requests", "Number of requests,\nper method and path.")
+//!     .unwrap();
+//! writer.metric_type("http_requests", MetricType::Counter).unwrap();
+//! writer
+//!     .sample(
+//!         "http_requests_total",
+//!         &Labels {
+//!             method: "GET",
+//!             path: "/metrics",
+//!         },
+//!         42u64,
+//!         Some(1_666_000_000_123),
+//!     )
+//!     .unwrap();
+//! writer.sample("up", &(), 1.0, None).unwrap();
+//!
+//! let serialized = writer.finish().unwrap();
+//!
+//! assert_eq!(
+//!     String::from_utf8(serialized).unwrap(),
+//!     concat!(
+//!         "# HELP http_requests Number of requests,\\nper method and path.\n",
+//!         "# TYPE http_requests counter\n",
+//!         "http_requests_total{method=\"GET\",path=\"/metrics\"} 42 1666000000.123\n",
+//!         "up 1.0\n",
+//!         "# EOF\n",
+//!     ),
+//! );
+//! ```
+//!
+//! Parsing:
+//!
+//! ```rust
+//! # use serde::Deserialize;
+//! # use serde_prometheus_labels::exposition::{MetricType, Parser};
+//! #
+//! #[derive(Debug, Deserialize, PartialEq)]
+//! struct Labels<'a> {
+//!     method: &'a str,
+//!     path: String,
+//! }
+//!
+//! let text = concat!(
+//!     "# HELP http_requests Number of requests.\n",
+//!     "# TYPE http_requests counter\n",
+//!     "http_requests_total{method=\"GET\",path=\"/\\\"quoted\\\"\"} 42 1666000000.123\n",
+//!     "# EOF\n",
+//! );
+//!
+//! let mut parser = Parser::open_metrics(text);
+//!
+//! assert_eq!(
+//!     parser.next().unwrap().unwrap(),
+//!     (
+//!         "http_requests_total",
+//!         Labels {
+//!             method: "GET",
+//!             path: "/\"quoted\"".to_owned(),
+//!         },
+//!         42.0,
+//!         Some(1_666_000_000_123),
+//!     ),
+//! );
+//! assert!(parser.next().is_none());
+//!
+//! assert_eq!(parser.help("http_requests"), Some("Number of requests."));
+//! assert_eq!(parser.metric_type("http_requests"), Some(MetricType::Counter));
+//! ```
+
+use crate::de::{self, LabelsDeserializer};
+use crate::top::check_metric_name;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::marker::PhantomData;
+use std::{error, fmt, io, str};
+
+/// A writer of exposition lines.
+#[derive(Debug)]
+pub struct Writer<W> {
+    writer: W,
+    format: Format,
+    buf: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Format {
+    Prometheus,
+    OpenMetrics,
+}
+
+impl<W> Writer<W>
+where
+    W: Write,
+{
+    /// Creates a writer of the Prometheus text format.
+    pub fn new(writer: W) -> Self {
+        Self::with_format(writer, Format::Prometheus)
+    }
+
+    /// Creates a writer of the OpenMetrics text format.
+    pub fn open_metrics(writer: W) -> Self {
+        Self::with_format(writer, Format::OpenMetrics)
+    }
+
+    fn with_format(writer: W, format: Format) -> Self {
+        Self {
+            writer,
+            format,
+            buf: vec![],
+        }
+    }
+
+    /// Writes the `HELP` line of the metric family `name`.
+    pub fn help(&mut self, name: &str, help: &str) -> Result<(), Error> {
+        check_metric_name(name)?;
+
+        self.write_all(b"# HELP ")?;
+        self.write_all(name.as_bytes())?;
+        self.write_all(b" ")?;
+
+        let mut rest = help;
+
+        while let Some(i) = rest.find(['\\', '\n', '"']) {
+            let (chunk, escaped) = rest.split_at(i);
+
+            self.write_all(chunk.as_bytes())?;
+
+            let escape: &[u8] = match escaped.as_bytes()[0] {
+                b'\\' => b"\\\\",
+                b'\n' => b"\\n",
+                // Only OpenMetrics escapes double quotes in help text.
+                _ if self.format == Format::OpenMetrics => b"\\\"",
+                _ => b"\"",
+            };
+            self.write_all(escape)?;
+
+            rest = &escaped[1..];
+        }
+
+        self.write_all(rest.as_bytes())?;
+        self.write_all(b"\n")
+    }
+
+    /// Writes the `TYPE` line of the metric family `name`.
+    ///
+    /// Fails if `metric_type` isn't supported by the format of this writer.
+    pub fn metric_type(&mut self, name: &str, metric_type: MetricType) -> Result<(), Error> {
+        check_metric_name(name)?;
+
+        let metric_type = match (metric_type, self.format) {
+            (MetricType::Counter, _) => "counter",
+            (MetricType::Gauge, _) => "gauge",
+            (MetricType::Histogram, _) => "histogram",
+            (MetricType::Summary, _) => "summary",
+            (MetricType::Unknown, Format::Prometheus) => "untyped",
+            (MetricType::Unknown, Format::OpenMetrics) => "unknown",
+            (MetricType::GaugeHistogram, Format::OpenMetrics) => "gaugehistogram",
+            (MetricType::Info, Format::OpenMetrics) => "info",
+            (MetricType::StateSet, Format::OpenMetrics) => "stateset",
+            (metric_type, Format::Prometheus) => return Err(unsupported_metric_type(metric_type)),
+        };
+
+        self.write_all(b"# TYPE ")?;
+        self.write_all(name.as_bytes())?;
+        self.write_all(b" ")?;
+        self.write_all(metric_type.as_bytes())?;
+        self.write_all(b"\n")
+    }
+
+    /// Writes a sample of the series `name` with the labels of `label_set`.
+    ///
+    /// The timestamp is in milliseconds since the Unix epoch, and is written
+    /// in seconds in the OpenMetrics format.
+    ///
+    /// See [`serializer`](crate::serializer) for information about the data
+    /// format of `label_set`.
+    pub fn sample(
+        &mut self,
+        name: &str,
+        label_set: &impl Serialize,
+        value: impl Value,
+        timestamp: Option<i64>,
+    ) -> Result<(), Error> {
+        check_metric_name(name)?;
+
+        self.buf.clear();
+        crate::to_writer(&mut self.buf, label_set)?;
+
+        self.write_all(name.as_bytes())?;
+
+        if !self.buf.is_empty() {
+            self.writer.write_all(b"{").map_err(Error::new)?;
+            self.writer.write_all(&self.buf).map_err(Error::new)?;
+            self.writer.write_all(b"}").map_err(Error::new)?;
+        }
+
+        self.write_all(b" ")?;
+        value.write(&mut self.writer).map_err(Error::new)?;
+
+        if let Some(timestamp) = timestamp {
+            self.write_all(b" ")?;
+
+            let mut buf = itoa::Buffer::new();
+
+            match self.format {
+                Format::Prometheus => self.write_all(buf.format(timestamp).as_bytes())?,
+                Format::OpenMetrics => {
+                    let (secs, millis) = (timestamp.div_euclid(1000), timestamp.rem_euclid(1000));
+
+                    self.write_all(buf.format(secs).as_bytes())?;
+                    write!(self.writer, ".{millis:03}").map_err(Error::new)?;
+                }
+            }
+        }
+
+        self.write_all(b"\n")
+    }
+
+    /// Finishes the exposition, writing the `# EOF` terminator in the
+    /// OpenMetrics format, and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W, Error> {
+        if self.format == Format::OpenMetrics {
+            self.write_all(b"# EOF\n")?;
+        }
+
+        Ok(self.writer)
+    }
+
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.writer.write_all(bytes).map_err(Error::new)
+    }
+}
+
+/// A parser of exposition text, which iterates over its samples as
+/// `(name, label set, value, timestamp)` tuples.
+///
+/// Label sets are deserialized like with [`from_str`](crate::from_str), and
+/// timestamps are in milliseconds since the Unix epoch.
+///
+/// The `HELP` and `TYPE` lines are collected as the parser goes through
+/// the text, and can be queried with [`Parser::help`] and
+/// [`Parser::metric_type`].
+pub struct Parser<'a, T> {
+    lines: str::Lines<'a>,
+    format: Format,
+    families: HashMap<&'a str, Metadata>,
+    eof: bool,
+    marker: PhantomData<fn() -> T>,
+}
+
+#[derive(Default)]
+struct Metadata {
+    help: Option<String>,
+    metric_type: Option<MetricType>,
+}
+
+impl<'a, T> Parser<'a, T> {
+    /// Creates a parser of the Prometheus text format.
+    pub fn new(text: &'a str) -> Self {
+        Self::with_format(text, Format::Prometheus)
+    }
+
+    /// Creates a parser of the OpenMetrics text format, which must end with
+    /// `# EOF`.
+    pub fn open_metrics(text: &'a str) -> Self {
+        Self::with_format(text, Format::OpenMetrics)
+    }
+
+    fn with_format(text: &'a str, format: Format) -> Self {
+        Self {
+            lines: text.lines(),
+            format,
+            families: HashMap::new(),
+            eof: false,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the help of the metric family `name`, if its `HELP` line was
+    /// parsed already.
+    pub fn help(&self, name: &str) -> Option<&str> {
+        self.families.get(name)?.help.as_deref()
+    }
+
+    /// Returns the type of the metric family `name`, if its `TYPE` line was
+    /// parsed already.
+    pub fn metric_type(&self, name: &str) -> Option<MetricType> {
+        self.families.get(name)?.metric_type
+    }
+
+    fn parse_comment(&mut self, line: &'a str, comment: &'a str) -> Result<(), Error> {
+        if self.format == Format::OpenMetrics && comment == " EOF" {
+            self.eof = true;
+            return Ok(());
+        }
+
+        let (keyword, rest) = split_token(comment.trim_start_matches(BLANKS));
+        let (name, rest) = split_token(rest);
+
+        match (keyword, name, rest) {
+            ("HELP", name, help) if !name.is_empty() => {
+                let help = unescape_help(help, self.format)
+                    .ok_or_else(|| invalid_line(line, "invalid escape sequence in help"))?;
+
+                self.families.entry(name).or_default().help = Some(help);
+            }
+            ("TYPE", name, metric_type) if !name.is_empty() => {
+                let metric_type = match (metric_type.trim_end_matches(BLANKS), self.format) {
+                    ("counter", _) => MetricType::Counter,
+                    ("gauge", _) => MetricType::Gauge,
+                    ("histogram", _) => MetricType::Histogram,
+                    ("summary", _) => MetricType::Summary,
+                    ("untyped", Format::Prometheus) => MetricType::Unknown,
+                    ("unknown", Format::OpenMetrics) => MetricType::Unknown,
+                    ("gaugehistogram", Format::OpenMetrics) => MetricType::GaugeHistogram,
+                    ("info", Format::OpenMetrics) => MetricType::Info,
+                    ("stateset", Format::OpenMetrics) => MetricType::StateSet,
+                    _ => return Err(invalid_line(line, "unknown metric type")),
+                };
+
+                self.families.entry(name).or_default().metric_type = Some(metric_type);
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn parse_sample(
+        &self,
+        line: &'a str,
+    ) -> Result<(&'a str, de::Pairs<'a>, f64, Option<i64>), Error> {
+        let end = line
+            .find(['{', ' ', '\t'])
+            .ok_or_else(|| invalid_line(line, "missing value"))?;
+        let (name, rest) = line.split_at(end);
+        let mut rest = rest.trim_start_matches(BLANKS);
+
+        check_metric_name(name)?;
+
+        let pairs = match rest.strip_prefix('{') {
+            Some(mut labels) => {
+                let pairs = de::parse_pairs(&mut labels)?;
+
+                rest = labels
+                    .strip_prefix('}')
+                    .ok_or_else(|| invalid_line(line, "unterminated label set"))?;
+
+                pairs
+            }
+            None => vec![],
+        };
+
+        // Exemplars are ignored.
+        if self.format == Format::OpenMetrics {
+            rest = rest.split_once(" # ").map_or(rest, |(rest, _)| rest);
+        }
+
+        // Tokens are separated by runs of spaces and tabs.
+        let mut words = rest.split(BLANKS).filter(|word| !word.is_empty());
+
+        let value = words
+            .next()
+            .ok_or_else(|| invalid_line(line, "missing value"))?
+            .parse()
+            .map_err(|_| invalid_line(line, "invalid value"))?;
+
+        let timestamp = words
+            .next()
+            .map(|timestamp| match self.format {
+                Format::Prometheus => timestamp.parse().ok(),
+                Format::OpenMetrics => timestamp
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|secs| secs.is_finite())
+                    .map(|secs| (secs * 1000.0).round() as i64),
+            })
+            .map(|timestamp| timestamp.ok_or_else(|| invalid_line(line, "invalid timestamp")))
+            .transpose()?;
+
+        if words.next().is_some() {
+            return Err(invalid_line(line, "trailing characters"));
+        }
+
+        Ok((name, pairs, value, timestamp))
+    }
+}
+
+impl<'a, T> Iterator for Parser<'a, T>
+where
+    T: Deserialize<'a>,
+{
+    type Item = Result<(&'a str, T, f64, Option<i64>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next() {
+                Some(line) => line,
+                None if self.format == Format::OpenMetrics && !self.eof => {
+                    // Only report the missing terminator once.
+                    self.eof = true;
+                    return Some(Err(invalid_line("", "missing `# EOF`")));
+                }
+                None => return None,
+            };
+
+            if self.eof && self.format == Format::OpenMetrics {
+                self.lines = "".lines();
+                return Some(Err(invalid_line(line, "line after `# EOF`")));
+            }
+
+            let line = line.trim_start_matches(BLANKS);
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(comment) = line.strip_prefix('#') {
+                if let Err(error) = self.parse_comment(line, comment) {
+                    return Some(Err(error));
+                }
+
+                continue;
+            }
+
+            return Some(
+                self.parse_sample(line)
+                    .and_then(|(name, pairs, value, timestamp)| {
+                        let label_set = T::deserialize(LabelsDeserializer::new(pairs))?;
+
+                        Ok((name, label_set, value, timestamp))
+                    }),
+            );
+        }
+    }
+}
+
+impl<T> fmt::Debug for Parser<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Parser")
+            .field("format", &self.format)
+            .field("eof", &self.eof)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The characters separating the tokens of a line.
+const BLANKS: [char; 2] = [' ', '\t'];
+
+/// Splits the first token of `s` from the rest, without its leading
+/// blanks.
+fn split_token(s: &str) -> (&str, &str) {
+    match s.split_once(BLANKS) {
+        Some((token, rest)) => (token, rest.trim_start_matches(BLANKS)),
+        None => (s, ""),
+    }
+}
+
+/// Unescapes the help text of a `HELP` line.
+///
+/// The Prometheus text format only escapes backslashes and line feeds,
+/// while OpenMetrics also escapes double quotes.
+fn unescape_help(help: &str, format: Format) -> Option<String> {
+    let mut unescaped = String::with_capacity(help.len());
+    let mut chars = help.chars();
+
+    while let Some(c) = chars.next() {
+        unescaped.push(match c {
+            '\\' => match (chars.next()?, format) {
+                ('n', _) => '\n',
+                ('\\', _) => '\\',
+                ('"', Format::OpenMetrics) => '"',
+                _ => return None,
+            },
+            c => c,
+        });
+    }
+
+    Some(unescaped)
+}
+
+/// The type of a metric family.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MetricType {
+    Counter,
+    Gauge,
+    Histogram,
+    Summary,
+    Unknown,
+    /// Only supported by OpenMetrics.
+    GaugeHistogram,
+    /// Only supported by OpenMetrics.
+    Info,
+    /// Only supported by OpenMetrics.
+    StateSet,
+}
+
+/// The value of a sample.
+///
+/// This trait is sealed and implemented for the primitive integer and
+/// floating-point types.
+pub trait Value: private::Sealed {
+    #[doc(hidden)]
+    fn write(self, writer: &mut dyn Write) -> io::Result<()>;
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+macro_rules! integer_values {
+    ($($ty:ty),*) => {$(
+        impl private::Sealed for $ty {}
+
+        impl Value for $ty {
+            fn write(self, writer: &mut dyn Write) -> io::Result<()> {
+                writer.write_all(itoa::Buffer::new().format(self).as_bytes())
+            }
+        }
+    )*}
+}
+
+macro_rules! float_values {
+    ($($ty:ty),*) => {$(
+        impl private::Sealed for $ty {}
+
+        impl Value for $ty {
+            fn write(self, writer: &mut dyn Write) -> io::Result<()> {
+                let value = if self.is_nan() {
+                    "NaN"
+                } else if self == <$ty>::INFINITY {
+                    "+Inf"
+                } else if self == <$ty>::NEG_INFINITY {
+                    "-Inf"
+                } else {
+                    return writer.write_all(ryu::Buffer::new().format_finite(self).as_bytes());
+                };
+
+                writer.write_all(value.as_bytes())
+            }
+        }
+    )*}
+}
+
+integer_values!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+float_values!(f32, f64);
+
+fn invalid_line(line: &str, reason: &'static str) -> Error {
+    #[derive(Debug)]
+    struct InvalidLineError {
+        line: String,
+        reason: &'static str,
+    }
+
+    impl error::Error for InvalidLineError {
+        #[allow(deprecated)]
+        fn description(&self) -> &str {
+            "invalid exposition line"
+        }
+    }
+
+    impl fmt::Display for InvalidLineError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "invalid exposition line {:?} ({})",
+                self.line, self.reason
+            )
+        }
+    }
+
+    Error::new(io::Error::new(
+        io::ErrorKind::InvalidData,
+        InvalidLineError {
+            line: line.to_owned(),
+            reason,
+        },
+    ))
+}
+
+fn unsupported_metric_type(metric_type: MetricType) -> Error {
+    #[derive(Debug)]
+    struct UnsupportedMetricTypeError(MetricType);
+
+    impl error::Error for UnsupportedMetricTypeError {
+        #[allow(deprecated)]
+        fn description(&self) -> &str {
+            "metric type unsupported by the Prometheus text format"
+        }
+    }
+
+    impl fmt::Display for UnsupportedMetricTypeError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "metric type unsupported by the Prometheus text format ({:?})",
+                self.0,
+            )
+        }
+    }
+
+    Error::invalid_input(UnsupportedMetricTypeError(metric_type))
+}
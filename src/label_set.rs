@@ -0,0 +1,215 @@
+use crate::Error;
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+use serde::de::{self, Deserialize, Deserializer, MapAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+/// An owned set of labels, sorted by name and without duplicate names.
+///
+/// Label sets are serialized as maps, so they can be passed anywhere this
+/// crate expects a label set, and they are written in the same format as
+/// [`to_string`](crate::to_string) by their [`Display`](fmt::Display)
+/// implementation.
+///
+/// #### Examples
+///
+/// ```rust
+/// # use serde::Serialize;
+/// # use serde_prometheus_labels::{to_label_set, LabelSet};
+/// #
+/// #[derive(Serialize)]
+/// struct Labels {
+///     path: &'static str,
+///     method: &'static str,
+/// }
+///
+/// let mut label_set = to_label_set(&Labels {
+///     path: "/metrics",
+///     method: "GET",
+/// })
+/// .unwrap();
+///
+/// label_set.insert("region", "eu-west-3").unwrap();
+/// label_set.remove("path");
+///
+/// assert_eq!(label_set.to_string(), r#"method="GET",region="eu-west-3""#);
+/// assert_eq!(label_set, r#"region="eu-west-3",method="GET""#.parse().unwrap());
+/// ```
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct LabelSet {
+    labels: Vec<(String, String)>,
+}
+
+impl LabelSet {
+    /// Creates an empty label set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn from_pairs(
+        pairs: impl IntoIterator<Item = (Cow<'static, str>, String)>,
+    ) -> Result<Self, Error> {
+        let mut labels = pairs
+            .into_iter()
+            .map(|(name, value)| (name.into_owned(), value))
+            .collect::<Vec<_>>();
+
+        labels.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        if let Some(window) = labels.windows(2).find(|window| window[0].0 == window[1].0) {
+            return Err(crate::top::duplicate_key(&window[0].0));
+        }
+
+        Ok(Self { labels })
+    }
+
+    /// Returns the number of labels in this label set.
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    /// Returns whether this label set has no labels.
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// Returns the value of the label `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        let index = self.find(name).ok()?;
+
+        Some(&self.labels[index].1)
+    }
+
+    /// Returns an iterator over the names and values of the labels, sorted
+    /// by name.
+    pub fn iter(&self) -> impl '_ + ExactSizeIterator<Item = (&str, &str)> {
+        self.labels.iter().map(|(name, value)| (&**name, &**value))
+    }
+
+    /// Sets the label `name` to `value`, returning its previous value.
+    ///
+    /// Fails if `name` isn't a valid label name.
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Result<Option<String>, Error> {
+        let name = name.into();
+        crate::top::check_key(&name)?;
+
+        Ok(self.insert_unchecked(name, value.into()))
+    }
+
+    /// Removes the label `name`, returning its value.
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let index = self.find(name).ok()?;
+
+        Some(self.labels.remove(index).1)
+    }
+
+    /// Adds the labels of `other` to this label set.
+    ///
+    /// Fails without modifying this label set if both have a label with the
+    /// same name.
+    pub fn merge(&mut self, other: &LabelSet) -> Result<(), Error> {
+        if let Some((name, _)) = other.iter().find(|(name, _)| self.find(name).is_ok()) {
+            return Err(crate::top::duplicate_key(name));
+        }
+
+        self.override_with(other);
+
+        Ok(())
+    }
+
+    /// Adds the labels of `other` to this label set, replacing the values of
+    /// the labels they have in common.
+    pub fn override_with(&mut self, other: &LabelSet) {
+        for (name, value) in &other.labels {
+            self.insert_unchecked(name.clone(), value.clone());
+        }
+    }
+
+    fn insert_unchecked(&mut self, name: String, value: String) -> Option<String> {
+        match self.find(&name) {
+            Ok(index) => Some(core::mem::replace(&mut self.labels[index].1, value)),
+            Err(index) => {
+                self.labels.insert(index, (name, value));
+
+                None
+            }
+        }
+    }
+
+    fn find(&self, name: &str) -> Result<usize, usize> {
+        self.labels.binary_search_by(|(n, _)| (**n).cmp(name))
+    }
+}
+
+impl Serialize for LabelSet {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.labels.len()))?;
+
+        for (name, value) in &self.labels {
+            map.serialize_entry(name, value)?;
+        }
+
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for LabelSet {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct LabelSetVisitor;
+
+        impl<'de> Visitor<'de> for LabelSetVisitor {
+            type Value = LabelSet;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a map of label names to label values")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<LabelSet, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut pairs = vec![];
+
+                while let Some((name, value)) = map.next_entry::<String, String>()? {
+                    crate::top::check_key(&name).map_err(de::Error::custom)?;
+                    pairs.push((Cow::Owned(name), value));
+                }
+
+                LabelSet::from_pairs(pairs).map_err(de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_map(LabelSetVisitor)
+    }
+}
+
+impl fmt::Display for LabelSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Label names are validated, so serialization can't fail.
+        crate::display(self).fmt(f)
+    }
+}
+
+impl FromStr for LabelSet {
+    type Err = Error;
+
+    /// Parses a label set in the format written by
+    /// [`to_string`](crate::to_string).
+    fn from_str(s: &str) -> Result<Self, Error> {
+        crate::from_str(s)
+    }
+}
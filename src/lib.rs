@@ -1,21 +1,68 @@
-use serde::ser::{Serialize, Serializer};
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::fmt;
+use serde::de::Deserialize;
+use serde::ser::Serialize;
+#[cfg(feature = "std")]
+use serde::ser::Serializer;
+#[cfg(feature = "std")]
 use std::io::Write;
 
 #[cfg(feature = "bridge")]
 mod bridge;
+mod de;
 mod error;
+#[cfg(feature = "std")]
+pub mod exposition;
+mod label_set;
+#[cfg(feature = "matcher")]
+pub mod matcher;
+mod output;
+mod pushgateway;
+#[cfg(any(feature = "matcher", feature = "relabel"))]
+mod re2;
+#[cfg(feature = "relabel")]
+pub mod relabel;
+#[cfg(feature = "remote-write")]
+pub mod remote_write;
+mod selector;
 mod str;
 mod top;
 mod value;
 
 #[cfg(feature = "bridge")]
-pub use self::bridge::Family;
+pub use self::bridge::{
+    Collector, CounterWithExemplar, Family, FamilyGroup, GroupMember, Histogram,
+    HistogramConstructor, HistogramWithExemplars, Info, LabelSetRef, MetricGroup, ShardedFamily,
+    TextfileWriter,
+};
+
+#[cfg(feature = "protobuf")]
+pub use self::bridge::protobuf;
 
 pub use self::error::Error;
+pub use self::label_set::LabelSet;
+pub use self::output::{FmtOutput, Output};
+
+#[cfg(feature = "std")]
+pub use self::output::IoOutput;
+
+#[cfg(feature = "bytes")]
+pub use self::output::BufMutOutput;
+pub use self::selector::{NotEqual, NotRegexMatch, RegexMatch};
 
 /// Serializes `value` into a [`String`].
 ///
-/// See [`serializer`] for information about the data format.
+#[cfg_attr(
+    feature = "std",
+    doc = "See [`serializer`] for information about the data format."
+)]
 ///
 /// #### Examples
 ///
@@ -70,17 +117,16 @@ pub use self::error::Error;
 /// ```
 pub fn to_string(value: &impl Serialize) -> Result<String, Error> {
     let mut string = "".to_owned();
-
-    value.serialize(top::TopSerializer::new(str::Writer::from_mut_string(
-        &mut string,
-    )))?;
-
+    to_output(&mut string, value)?;
     Ok(string)
 }
 
 /// Serializes `value` into a [`Vec<u8>`].
 ///
-/// See [`serializer`] for information about the data format.
+#[cfg_attr(
+    feature = "std",
+    doc = "See [`serializer`] for information about the data format."
+)]
 ///
 /// #### Examples
 ///
@@ -113,22 +159,370 @@ pub fn to_string(value: &impl Serialize) -> Result<String, Error> {
 /// ```
 pub fn to_vec(value: &impl Serialize) -> Result<Vec<u8>, Error> {
     let mut buf = vec![];
-    to_writer(&mut buf, value)?;
+    to_output(&mut buf, value)?;
     Ok(buf)
 }
 
+/// Returns an adapter displaying the labels of `value`, in the format of
+/// [`to_string`], without allocating.
+///
+#[cfg_attr(
+    feature = "std",
+    doc = "See [`serializer`] for information about the data format."
+)]
+///
+/// Serialization errors are reported as [`fmt::Error`], which makes
+/// formatting macros such as [`format!`] panic, so `value` should be known
+/// to serialize successfully.
+///
+/// #### Examples
+///
+/// ```rust
+/// # use serde::Serialize;
+/// # use serde_prometheus_labels::display;
+/// #
+/// #[derive(Serialize)]
+/// struct Labels {
+///     method: &'static str,
+///     path: &'static str,
+/// }
+///
+/// let labels = Labels {
+///     method: "GET",
+///     path: "/metrics",
+/// };
+///
+/// assert_eq!(
+///     format!("unexpected request {{{}}}", display(&labels)),
+///     r#"unexpected request {method="GET",path="/metrics"}"#,
+/// );
+/// ```
+pub fn display<T>(value: &T) -> impl '_ + fmt::Display
+where
+    T: ?Sized + Serialize,
+{
+    top::DisplayLabels(value)
+}
+
 /// Serializes `value` into [`writer`][Write].
 ///
-/// See [`serializer`] for information about the data format.
+#[cfg_attr(
+    feature = "std",
+    doc = "See [`serializer`] for information about the data format."
+)]
+#[cfg(feature = "std")]
 pub fn to_writer(writer: &mut (impl ?Sized + Write), value: &impl Serialize) -> Result<(), Error> {
     value.serialize(serializer(writer))
 }
 
+/// Serializes `value` into `output`.
+///
+#[cfg_attr(
+    feature = "std",
+    doc = "See [`serializer`] for information about the data format."
+)]
+///
+/// #### Examples
+///
+/// ```rust
+/// # use serde::Serialize;
+/// # use serde_prometheus_labels::to_output;
+/// #
+/// #[derive(Serialize)]
+/// struct Labels {
+///     method: &'static str,
+///     path: &'static str,
+/// }
+///
+/// let mut line = "http_requests_total{".to_owned();
+///
+/// to_output(
+///     &mut line,
+///     &Labels {
+///         method: "GET",
+///         path: "/metrics",
+///     },
+/// )
+/// .unwrap();
+///
+/// assert_eq!(line, r#"http_requests_total{method="GET",path="/metrics""#);
+/// ```
+pub fn to_output(output: impl Output, value: &impl Serialize) -> Result<(), Error> {
+    value.serialize(top::TopSerializer::new(top::LabelWriter::new(
+        str::Writer::new(output),
+    )))
+}
+
+/// Serializes `value` into a [`LabelSet`].
+///
+#[cfg_attr(
+    feature = "std",
+    doc = "See [`serializer`] for information about the data format."
+)]
+///
+/// Fails if `value` has duplicate keys.
+pub fn to_label_set(value: &impl Serialize) -> Result<LabelSet, Error> {
+    LabelSet::from_pairs(top::pairs(value)?)
+}
+
+/// Serializes `value` into the path of a Pushgateway grouping key for the
+/// job `job`, such as `/metrics/job/backup/instance/unionize.org`.
+///
+#[cfg_attr(
+    feature = "std",
+    doc = "See [`serializer`] for information about the data format."
+)]
+///
+/// The empty label values, and the job and label values which contain
+/// characters that would need to be escaped in a URL path, such as `/`, are
+/// encoded in URL-safe base64 with the `@base64` suffix on their key, as
+/// specified by the [Pushgateway documentation][doc].
+///
+/// Fails if `job` is empty or if `value` has a `job` label.
+///
+/// [doc]: https://github.com/prometheus/pushgateway/blob/master/README.md#url
+///
+/// #### Examples
+///
+/// ```rust
+/// # use serde::Serialize;
+/// # use serde_prometheus_labels::to_pushgateway_path;
+/// #
+/// #[derive(Serialize)]
+/// struct Labels {
+///     instance: &'static str,
+///     path: &'static str,
+///     shard: Option<u32>,
+/// }
+///
+/// let labels = Labels {
+///     instance: "unionize.org",
+///     path: "/var/tmp",
+///     shard: None,
+/// };
+///
+/// let path = to_pushgateway_path("backup", &labels).unwrap();
+///
+/// assert_eq!(
+///     path,
+///     "/metrics/job/backup/instance/unionize.org/path@base64/L3Zhci90bXA=/shard@base64/=",
+/// );
+/// ```
+pub fn to_pushgateway_path(job: &str, value: &impl Serialize) -> Result<String, Error> {
+    if job.is_empty() {
+        return Err(pushgateway::empty_job());
+    }
+
+    let mut path = "/metrics".to_owned();
+    pushgateway::push_segment(&mut path, "job", job);
+
+    for (key, value) in top::pairs(value)? {
+        if key == "job" {
+            return Err(top::duplicate_key(&key));
+        }
+
+        pushgateway::push_segment(&mut path, &key, &value);
+    }
+
+    Ok(path)
+}
+
+/// Serializes `value` into a PromQL series selector for the metric `name`,
+/// such as `http_requests_total{method="GET",status!="200"}`.
+///
+#[cfg_attr(
+    feature = "std",
+    doc = "See [`serializer`] for information about the data format."
+)]
+///
+/// Labels are matched for equality, unless their value is wrapped in
+/// [`NotEqual`], [`RegexMatch`] or [`NotRegexMatch`]. Labels whose value is
+/// `None` are skipped rather than matched against the empty string.
+///
+/// `name` may be empty, in which case the selector only has label matchers,
+/// and fails if there are none.
+///
+/// #### Examples
+///
+/// ```rust
+/// # use serde::Serialize;
+/// # use serde_prometheus_labels::{to_selector, NotEqual, RegexMatch};
+/// #
+/// #[derive(Serialize)]
+/// struct Labels {
+///     method: &'static str,
+///     path: RegexMatch<&'static str>,
+///     status: NotEqual<u16>,
+///     instance: Option<&'static str>,
+/// }
+///
+/// let labels = Labels {
+///     method: "GET",
+///     path: RegexMatch(r"/api/\d+"),
+///     status: NotEqual(200),
+///     instance: None,
+/// };
+///
+/// let selector = to_selector("http_requests_total", &labels).unwrap();
+///
+/// assert_eq!(
+///     selector,
+///     r#"http_requests_total{method="GET",path=~"/api/\\d+",status!="200"}"#,
+/// );
+/// ```
+pub fn to_selector(name: &str, value: &impl Serialize) -> Result<String, Error> {
+    if !name.is_empty() {
+        top::check_metric_name(name)?;
+    }
+
+    let mut matchers = "".to_owned();
+    value.serialize(top::TopSerializer::new(selector::MatcherWriter::new(
+        &mut matchers,
+    )))?;
+
+    if name.is_empty() && matchers.is_empty() {
+        return Err(selector::empty_selector());
+    }
+
+    let mut selector = name.to_owned();
+
+    if !matchers.is_empty() {
+        selector.push('{');
+        selector.push_str(&matchers);
+        selector.push('}');
+    }
+
+    Ok(selector)
+}
+
+/// Computes the fingerprint of `value`, as computed by `labels.Hash` in
+/// Prometheus for the series stored with these labels.
+///
+#[cfg_attr(
+    feature = "std",
+    doc = "See [`serializer`] for information about the data format."
+)]
+///
+/// The labels are sorted by name and hashed with xxHash64 as
+/// `name 0xff value 0xff` sequences. The metric name of a series can be
+/// included with a field renamed to `__name__`.
+///
+/// Unlike `labels.Hash`, which hashes every label it is given, this skips
+/// labels with empty values: Prometheus removes them before storing a
+/// series, so `{a="",b="1"}` has the fingerprint of `{b="1"}`.
+///
+/// Label sets of 1 KiB or more match as well: `labels.Hash` then streams
+/// the same bytes into an xxHash64 digest instead of hashing a buffer, which
+/// gives the same hash.
+///
+/// Fails if `value` has duplicate keys.
+///
+/// #### Examples
+///
+/// ```rust
+/// # use serde::Serialize;
+/// # use serde_prometheus_labels::fingerprint;
+/// # use std::collections::BTreeMap;
+/// #
+/// #[derive(Serialize)]
+/// struct Series {
+///     #[serde(rename = "__name__")]
+///     name: &'static str,
+///     job: &'static str,
+///     instance: &'static str,
+/// }
+///
+/// let series = Series {
+///     name: "up",
+///     job: "prometheus",
+///     instance: "",
+/// };
+///
+/// let labels = BTreeMap::from([("__name__", "up"), ("job", "prometheus")]);
+///
+/// assert_eq!(fingerprint(&series).unwrap(), fingerprint(&labels).unwrap());
+/// ```
+#[cfg(feature = "fingerprint")]
+pub fn fingerprint(value: &impl Serialize) -> Result<u64, Error> {
+    let mut pairs = top::pairs(value)?;
+    pairs.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    if let Some(window) = pairs.windows(2).find(|window| window[0].0 == window[1].0) {
+        return Err(top::duplicate_key(&window[0].0));
+    }
+
+    let mut hasher = xxhash_rust::xxh64::Xxh64::new(0);
+
+    for (key, value) in pairs.iter().filter(|(_, value)| !value.is_empty()) {
+        hasher.update(key.as_bytes());
+        hasher.update(&[0xff]);
+        hasher.update(value.as_bytes());
+        hasher.update(&[0xff]);
+    }
+
+    Ok(hasher.digest())
+}
+
+/// Deserializes a `T` from labels in the format written by [`to_string`].
+///
+/// Labels are deserialized as a map from their keys to their values. The
+/// values are strings, which are parsed when a number, a boolean or a char
+/// is expected, and empty values are deserialized as `None`.
+///
+/// #### Examples
+///
+/// ```rust
+/// # use serde::Deserialize;
+/// # use serde_prometheus_labels::from_str;
+/// #
+/// #[derive(Debug, Deserialize, PartialEq)]
+/// struct Labels<'a> {
+///     method: Method,
+///     path: &'a str,
+///     status: u16,
+///     reason: Option<String>,
+/// }
+///
+/// #[derive(Debug, Deserialize, PartialEq)]
+/// enum Method {
+///     #[serde(rename = "GET")]
+///     Get,
+/// }
+///
+/// let labels = from_str::<Labels>(r#"method="GET",path="/metrics",status="200",reason="""#).unwrap();
+///
+/// assert_eq!(
+///     labels,
+///     Labels {
+///         method: Method::Get,
+///         path: "/metrics",
+///         status: 200,
+///         reason: None,
+///     },
+/// );
+/// ```
+pub fn from_str<'de, T>(s: &'de str) -> Result<T, Error>
+where
+    T: Deserialize<'de>,
+{
+    let mut rest = s;
+    let pairs = de::parse_pairs(&mut rest)?;
+
+    if !rest.is_empty() {
+        return Err(serde::de::Error::custom(format!(
+            "invalid label set (trailing characters {rest:?})"
+        )));
+    }
+
+    T::deserialize(de::LabelsDeserializer::new(pairs))
+}
+
 /// A serializer for Prometheus labels.
 ///
-/// This serializer only supports structs.
+/// This serializer only supports structs and maps. Map keys are serialized
+/// like values, and must be valid label names.
 ///
-/// For struct fields, the supported values are scalars, strings, and bytes
+/// For struct fields and map values, the supported values are scalars, strings, and bytes
 /// that can be converted to strings. Nones and units are ignored, and unit
 /// variants are serialized as their name. Anything else results in an error.
 ///
@@ -136,8 +530,9 @@ pub fn to_writer(writer: &mut (impl ?Sized + Write), value: &impl Serialize) ->
 /// as specified by the [Prometheus documentation][doc].
 ///
 /// [doc]: https://github.com/prometheus/docs/blob/main/content/docs/instrumenting/exposition_formats.md#text-format-details
+#[cfg(feature = "std")]
 pub fn serializer(
     writer: &mut (impl ?Sized + Write),
 ) -> impl '_ + Serializer<Ok = (), Error = Error> {
-    top::TopSerializer::new(str::Writer::new(writer))
+    top::TopSerializer::new(top::LabelWriter::new(str::Writer::new(IoOutput(writer))))
 }
@@ -0,0 +1,440 @@
+//! Evaluation of PromQL label matchers against label sets.
+//!
+//! #### Examples
+//!
+//! ```rust
+//! # use serde::Serialize;
+//! # use serde_prometheus_labels::matcher::Selector;
+//! #
+//! #[derive(Serialize)]
+//! struct Labels {
+//!     env: &'static str,
+//!     code: u16,
+//! }
+//!
+//! let selector = r#"{env=~"prod|staging", code!="200"}"#
+//!     .parse::<Selector>()
+//!     .unwrap();
+//!
+//! let matches = |env, code| selector.matches(&Labels { env, code }).unwrap();
+//!
+//! assert!(matches("prod", 500));
+//! assert!(!matches("prod", 200));
+//! assert!(!matches("production", 500));
+//! ```
+
+use crate::Error;
+use regex::Regex;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::str::FromStr;
+use std::{error, fmt};
+
+/// A PromQL series selector, such as `http_requests_total{code!="200"}`.
+///
+/// Label sets match a selector if they match all of its matchers. As in
+/// Prometheus, the metric name of a selector is a matcher on the
+/// `__name__` label.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Selector {
+    matchers: Vec<Matcher>,
+}
+
+impl Selector {
+    /// Creates a selector from its matchers.
+    pub fn new(matchers: impl IntoIterator<Item = Matcher>) -> Self {
+        Self {
+            matchers: matchers.into_iter().collect(),
+        }
+    }
+
+    /// Returns the matchers of this selector.
+    pub fn matchers(&self) -> &[Matcher] {
+        &self.matchers
+    }
+
+    /// Returns whether `label_set` matches this selector.
+    ///
+    /// `label_set` is serialized as with [`to_string`](crate::to_string),
+    /// and missing labels are matched as empty values.
+    pub fn matches(&self, label_set: &impl Serialize) -> Result<bool, Error> {
+        let pairs = crate::top::pairs(label_set)?;
+
+        Ok(self.matches_pairs(&pairs))
+    }
+
+    /// Returns whether the series named `name` with the labels `label_set`
+    /// matches this selector.
+    ///
+    /// Fails if `label_set` already has a `__name__` label.
+    pub fn matches_series(&self, name: &str, label_set: &impl Serialize) -> Result<bool, Error> {
+        let mut pairs = crate::top::pairs(label_set)?;
+
+        if pairs.iter().any(|(key, _)| key == "__name__") {
+            return Err(crate::top::duplicate_key("__name__"));
+        }
+
+        pairs.push(("__name__".into(), name.to_owned()));
+
+        Ok(self.matches_pairs(&pairs))
+    }
+
+    fn matches_pairs(&self, pairs: &[(Cow<'static, str>, String)]) -> bool {
+        self.matchers.iter().all(|matcher| {
+            let value = pairs
+                .iter()
+                .find(|(key, _)| *key == matcher.name)
+                .map_or("", |(_, value)| value);
+
+            matcher.matches(value)
+        })
+    }
+}
+
+impl FromStr for Selector {
+    type Err = Error;
+
+    /// Parses a selector from the PromQL syntax.
+    ///
+    /// As in Prometheus, a selector must have a metric name or at least one
+    /// matcher which doesn't match empty values.
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut input = s.trim_start();
+        let mut matchers = vec![];
+
+        let name = parse_name(&mut input, |c| c == ':');
+
+        if !name.is_empty() {
+            matchers.push(Matcher::new("__name__", MatchOp::Equal, name)?);
+        }
+
+        input = input.trim_start();
+
+        if let Some(rest) = input.strip_prefix('{') {
+            input = rest.trim_start();
+
+            while !input.starts_with('}') {
+                matchers.push(parse_matcher(&mut input)?);
+
+                input = input.trim_start();
+                input = match input.strip_prefix(',') {
+                    Some(rest) => rest.trim_start(),
+                    None if input.starts_with('}') => input,
+                    None => return Err(syntax_error("expected `,` or `}` after matcher")),
+                };
+            }
+
+            input = input[1..].trim_start();
+        } else if name.is_empty() {
+            return Err(syntax_error("expected metric name or `{`"));
+        }
+
+        if !input.is_empty() {
+            return Err(syntax_error("trailing characters"));
+        }
+
+        if matchers.iter().all(|matcher| matcher.matches("")) {
+            return Err(syntax_error(
+                "selector must contain at least one non-empty matcher",
+            ));
+        }
+
+        Ok(Self { matchers })
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut matchers = &self.matchers[..];
+
+        if let Some((first, rest)) = matchers.split_first() {
+            if first.name == "__name__"
+                && first.op == MatchOp::Equal
+                && crate::top::check_metric_name(&first.value).is_ok()
+            {
+                f.write_str(&first.value)?;
+                matchers = rest;
+
+                if matchers.is_empty() {
+                    return Ok(());
+                }
+            }
+        }
+
+        f.write_str("{")?;
+
+        for (i, matcher) in matchers.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+
+            write!(f, "{matcher}")?;
+        }
+
+        f.write_str("}")
+    }
+}
+
+/// A PromQL label matcher, such as `env=~"prod|staging"`.
+#[derive(Clone, Debug)]
+pub struct Matcher {
+    name: String,
+    op: MatchOp,
+    value: String,
+    regex: Option<Regex>,
+}
+
+impl Matcher {
+    /// Creates a matcher of the label `name`.
+    ///
+    /// The value of regular expression matchers is fully anchored, as in
+    /// Prometheus: `prod|staging` matches `prod` but not `production`.
+    ///
+    /// Fails if `name` isn't a valid label name or if the regular
+    /// expression is invalid.
+    pub fn new(
+        name: impl Into<String>,
+        op: MatchOp,
+        value: impl Into<String>,
+    ) -> Result<Self, Error> {
+        let name = name.into();
+        let value = value.into();
+
+        let mut rest = &*name;
+
+        if parse_name(&mut rest, |_| false).is_empty() || !rest.is_empty() {
+            return Err(syntax_error("invalid label name"));
+        }
+
+        let regex = match op {
+            MatchOp::Equal | MatchOp::NotEqual => None,
+            MatchOp::RegexMatch | MatchOp::NotRegexMatch => Some(crate::re2::compile(&value)?),
+        };
+
+        Ok(Self {
+            name,
+            op,
+            value,
+            regex,
+        })
+    }
+
+    /// Returns the name of the label matched by this matcher.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the operator of this matcher.
+    pub fn op(&self) -> MatchOp {
+        self.op
+    }
+
+    /// Returns the value of this matcher, without the anchors added to
+    /// regular expressions.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Returns whether the label value `value` matches this matcher.
+    pub fn matches(&self, value: &str) -> bool {
+        let is_match = |value| {
+            self.regex
+                .as_ref()
+                .is_some_and(|regex| regex.is_match(value))
+        };
+
+        match self.op {
+            MatchOp::Equal => value == self.value,
+            MatchOp::NotEqual => value != self.value,
+            MatchOp::RegexMatch => is_match(value),
+            MatchOp::NotRegexMatch => !is_match(value),
+        }
+    }
+}
+
+impl PartialEq for Matcher {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.op == other.op && self.value == other.value
+    }
+}
+
+impl FromStr for Matcher {
+    type Err = Error;
+
+    /// Parses a single matcher from the PromQL syntax.
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut input = s.trim_start();
+        let matcher = parse_matcher(&mut input)?;
+
+        if !input.trim_start().is_empty() {
+            return Err(syntax_error("trailing characters"));
+        }
+
+        Ok(matcher)
+    }
+}
+
+impl fmt::Display for Matcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}\"", self.name, self.op)?;
+
+        for c in self.value.chars() {
+            match c {
+                '\\' => f.write_str("\\\\")?,
+                '"' => f.write_str("\\\"")?,
+                '\n' => f.write_str("\\n")?,
+                c => write!(f, "{c}")?,
+            }
+        }
+
+        f.write_str("\"")
+    }
+}
+
+/// The operator of a [`Matcher`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MatchOp {
+    /// `=`
+    Equal,
+    /// `!=`
+    NotEqual,
+    /// `=~`
+    RegexMatch,
+    /// `!~`
+    NotRegexMatch,
+}
+
+impl fmt::Display for MatchOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MatchOp::Equal => "=",
+            MatchOp::NotEqual => "!=",
+            MatchOp::RegexMatch => "=~",
+            MatchOp::NotRegexMatch => "!~",
+        })
+    }
+}
+
+/// Parses a label name, or a metric name if `extra` accepts `:`, from the
+/// start of `input`.
+fn parse_name<'a>(input: &mut &'a str, extra: impl Fn(char) -> bool) -> &'a str {
+    let end = input
+        .char_indices()
+        .find(|&(i, c)| {
+            !(c.is_ascii_alphabetic() || c == '_' || extra(c) || i > 0 && c.is_ascii_digit())
+        })
+        .map_or(input.len(), |(i, _)| i);
+
+    let (name, rest) = input.split_at(end);
+    *input = rest;
+
+    name
+}
+
+fn parse_matcher(input: &mut &str) -> Result<Matcher, Error> {
+    let name = parse_name(input, |_| false);
+
+    if name.is_empty() {
+        return Err(syntax_error("expected label name"));
+    }
+
+    *input = input.trim_start();
+
+    let op = [
+        ("=~", MatchOp::RegexMatch),
+        ("!~", MatchOp::NotRegexMatch),
+        ("!=", MatchOp::NotEqual),
+        ("=", MatchOp::Equal),
+    ]
+    .into_iter()
+    .find_map(|(token, op)| {
+        let rest = input.strip_prefix(token)?;
+        *input = rest;
+        Some(op)
+    })
+    .ok_or_else(|| syntax_error("expected matcher operator"))?;
+
+    *input = input.trim_start();
+
+    let value = parse_string(input)?;
+
+    Matcher::new(name, op, value)
+}
+
+/// Parses a PromQL string literal, quoted with `"`, `'` or backticks.
+fn parse_string(input: &mut &str) -> Result<String, Error> {
+    let mut chars = input.char_indices();
+
+    let quote = match chars.next() {
+        Some((_, quote @ ('"' | '\'' | '`'))) => quote,
+        _ => return Err(syntax_error("expected string literal")),
+    };
+
+    let mut value = String::new();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c == quote => {
+                *input = &input[i + 1..];
+
+                return Ok(value);
+            }
+            '\\' if quote != '`' => {
+                let c = match chars.next() {
+                    Some((_, 'a')) => '\x07',
+                    Some((_, 'b')) => '\x08',
+                    Some((_, 'f')) => '\x0c',
+                    Some((_, 'n')) => '\n',
+                    Some((_, 'r')) => '\r',
+                    Some((_, 't')) => '\t',
+                    Some((_, 'v')) => '\x0b',
+                    Some((_, c @ ('\\' | '"' | '\''))) => c,
+                    Some((_, prefix @ ('x' | 'u' | 'U'))) => {
+                        let len = match prefix {
+                            'x' => 2,
+                            'u' => 4,
+                            _ => 8,
+                        };
+
+                        let digits = (0..len)
+                            .map(|_| chars.next().map(|(_, c)| c))
+                            .collect::<Option<String>>();
+
+                        digits
+                            .and_then(|digits| u32::from_str_radix(&digits, 16).ok())
+                            .filter(|&code| prefix != 'x' || code < 0x80)
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| syntax_error("invalid escape sequence"))?
+                    }
+                    _ => return Err(syntax_error("invalid escape sequence")),
+                };
+
+                value.push(c);
+            }
+            '\n' if quote != '`' => break,
+            c => value.push(c),
+        }
+    }
+
+    Err(syntax_error("unterminated string literal"))
+}
+
+fn syntax_error(message: &'static str) -> Error {
+    #[derive(Debug)]
+    struct SyntaxError(&'static str);
+
+    impl error::Error for SyntaxError {
+        #[allow(deprecated)]
+        fn description(&self) -> &str {
+            "invalid selector"
+        }
+    }
+
+    impl fmt::Display for SyntaxError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "invalid selector ({})", self.0)
+        }
+    }
+
+    Error::invalid_input(SyntaxError(message))
+}
@@ -0,0 +1,133 @@
+use crate::Error;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
+
+/// A destination for serialized labels, to which only string slices are
+/// written.
+///
+/// It is implemented for [`String`] and [`Vec<u8>`], and for other writers
+/// through the [`FmtOutput`] wrapper and, when the feature "std" is enabled,
+#[cfg_attr(feature = "std", doc = "the [`IoOutput`] wrapper.")]
+#[cfg_attr(not(feature = "std"), doc = "the `IoOutput` wrapper.")]
+///
+/// #### Examples
+///
+/// ```rust
+/// # use serde::Serialize;
+/// # use serde_prometheus_labels::{to_output, FmtOutput};
+/// # use std::fmt::Write;
+/// #
+/// #[derive(Serialize)]
+/// struct Labels {
+///     path: &'static str,
+/// }
+///
+/// let mut line = String::new();
+/// write!(line, "http_requests_total{{").unwrap();
+/// to_output(FmtOutput(&mut line), &Labels { path: "/metrics" }).unwrap();
+/// write!(line, "}} 1").unwrap();
+///
+/// assert_eq!(line, r#"http_requests_total{path="/metrics"} 1"#);
+/// ```
+pub trait Output {
+    /// Writes the whole string slice `s`.
+    fn write_str(&mut self, s: &str) -> Result<(), Error>;
+}
+
+impl<O> Output for &mut O
+where
+    O: ?Sized + Output,
+{
+    #[inline]
+    fn write_str(&mut self, s: &str) -> Result<(), Error> {
+        (**self).write_str(s)
+    }
+}
+
+impl Output for String {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> Result<(), Error> {
+        self.push_str(s);
+
+        Ok(())
+    }
+}
+
+impl Output for Vec<u8> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> Result<(), Error> {
+        self.extend_from_slice(s.as_bytes());
+
+        Ok(())
+    }
+}
+
+/// An [`Output`] writing to an [`io::Write`].
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IoOutput<W>(pub W);
+
+#[cfg(feature = "std")]
+impl<W> Output for IoOutput<W>
+where
+    W: io::Write,
+{
+    #[inline]
+    fn write_str(&mut self, s: &str) -> Result<(), Error> {
+        self.0.write_all(s.as_bytes()).map_err(Error::new)
+    }
+}
+
+/// An [`Output`] writing to a [`fmt::Write`], such as a
+/// [`fmt::Formatter`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FmtOutput<W>(pub W);
+
+impl<W> Output for FmtOutput<W>
+where
+    W: fmt::Write,
+{
+    #[inline]
+    fn write_str(&mut self, s: &str) -> Result<(), Error> {
+        self.0
+            .write_str(s)
+            .map_err(|fmt::Error| Error::other("formatter error"))
+    }
+}
+
+/// An [`Output`] writing to a [`bytes::BufMut`].
+///
+/// Writing fails if the buffer doesn't have enough remaining capacity.
+#[cfg(feature = "bytes")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BufMutOutput<B>(pub B);
+
+#[cfg(feature = "bytes")]
+impl<B> Output for BufMutOutput<B>
+where
+    B: bytes::BufMut,
+{
+    #[inline]
+    fn write_str(&mut self, s: &str) -> Result<(), Error> {
+        if self.0.remaining_mut() < s.len() {
+            return Err(Error::write_zero());
+        }
+
+        self.0.put_slice(s.as_bytes());
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl Output for bytes::BytesMut {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> Result<(), Error> {
+        self.extend_from_slice(s.as_bytes());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use crate::Error;
+use alloc::string::String;
+use core::{error, fmt};
+
+/// Pushes `/key/value` to `path`, encoding `value` in base64 if needed.
+pub(crate) fn push_segment(path: &mut String, key: &str, value: &str) {
+    path.push('/');
+    path.push_str(key);
+
+    if !value.is_empty() && value.bytes().all(is_unreserved) {
+        path.push('/');
+        path.push_str(value);
+        return;
+    }
+
+    path.push_str("@base64/");
+
+    if value.is_empty() {
+        // The Pushgateway expects a lone padding character for empty values.
+        path.push('=');
+        return;
+    }
+
+    push_base64(path, value.as_bytes());
+}
+
+/// Whether `byte` can appear unescaped in a URL path, as per RFC 3986.
+fn is_unreserved(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~')
+}
+
+/// Pushes `bytes` to `path` in URL-safe base64, with padding.
+fn push_base64(path: &mut String, bytes: &[u8]) {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &byte)| n | (byte as u32) << (16 - 8 * i));
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                path.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                path.push('=');
+            }
+        }
+    }
+}
+
+pub(crate) fn empty_job() -> Error {
+    #[derive(Debug)]
+    struct EmptyJobError;
+
+    impl error::Error for EmptyJobError {
+        #[allow(deprecated)]
+        fn description(&self) -> &str {
+            "empty job"
+        }
+    }
+
+    impl fmt::Display for EmptyJobError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("empty job (the Pushgateway requires a job name)")
+        }
+    }
+
+    Error::invalid_input(EmptyJobError)
+}
@@ -0,0 +1,47 @@
+use crate::Error;
+use alloc::string::String;
+use regex::Regex;
+
+/// Compiles `pattern`, anchored at both ends, with the semantics of RE2 as
+/// used by Prometheus.
+///
+/// Unlike in the `regex` crate, the Perl classes `\d`, `\s` and `\w` and
+/// the word boundaries `\b` and `\B` of RE2 are ASCII-only, so they are
+/// rewritten to their ASCII forms.
+pub(crate) fn compile(pattern: &str) -> Result<Regex, Error> {
+    let mut rewritten = String::with_capacity(pattern.len() + 10);
+    let mut chars = pattern.chars();
+
+    rewritten.push_str("^(?s:");
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            rewritten.push(c);
+            continue;
+        }
+
+        // Nested classes are also valid in classes.
+        match chars.next() {
+            Some('d') => rewritten.push_str("[0-9]"),
+            Some('D') => rewritten.push_str("[^0-9]"),
+            Some('s') => rewritten.push_str(r"[\t\n\f\r ]"),
+            Some('S') => rewritten.push_str(r"[^\t\n\f\r ]"),
+            Some('w') => rewritten.push_str("[0-9A-Za-z_]"),
+            Some('W') => rewritten.push_str("[^0-9A-Za-z_]"),
+            Some(c @ ('b' | 'B')) => {
+                rewritten.push_str(r"(?-u:\");
+                rewritten.push(c);
+                rewritten.push(')');
+            }
+            Some(c) => {
+                rewritten.push('\\');
+                rewritten.push(c);
+            }
+            None => rewritten.push('\\'),
+        }
+    }
+
+    rewritten.push_str(")$");
+
+    Regex::new(&rewritten).map_err(Error::invalid_input)
+}
@@ -0,0 +1,522 @@
+//! Relabeling of label sets with the rules of the `relabel_config`
+//! sections of Prometheus.
+//!
+//! #### Examples
+//!
+//! ```rust
+//! # use serde::Serialize;
+//! # use serde_prometheus_labels::relabel::Relabeler;
+//! #
+//! #[derive(Serialize)]
+//! struct Labels {
+//!     env: &'static str,
+//!     user_id: u64,
+//!     path: &'static str,
+//! }
+//!
+//! let relabeler = Relabeler::from_yaml(
+//!     r#"
+//! - action: drop
+//!   source_labels: [env]
+//!   regex: test
+//! - action: labeldrop
+//!   regex: user_.*
+//! - source_labels: [path]
+//!   regex: "/api/([^/]+).*"
+//!   target_label: endpoint
+//! "#,
+//! )
+//! .unwrap();
+//!
+//! let labels = Labels {
+//!     env: "prod",
+//!     user_id: 42,
+//!     path: "/api/orders/7",
+//! };
+//!
+//! assert_eq!(
+//!     relabeler.relabel_label_set(&labels).unwrap().unwrap(),
+//!     [
+//!         ("env".into(), "prod".to_owned()),
+//!         ("path".into(), "/api/orders/7".to_owned()),
+//!         ("endpoint".into(), "orders".to_owned()),
+//!     ],
+//! );
+//!
+//! let labels = Labels { env: "test", ..labels };
+//!
+//! assert_eq!(relabeler.relabel_label_set(&labels).unwrap(), None);
+//! ```
+
+use crate::Error;
+use md5::{Digest, Md5};
+use regex::{Captures, Regex};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::borrow::Cow;
+use std::{error, fmt};
+
+/// The labels of a label set, in the order they are written.
+pub type Labels = Vec<(Cow<'static, str>, String)>;
+
+/// Applies a sequence of relabeling rules to label sets.
+#[derive(Clone, Debug)]
+pub struct Relabeler {
+    rules: Vec<Rule>,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    config: RelabelConfig,
+    regex: Regex,
+}
+
+impl Relabeler {
+    /// Creates a relabeler applying `configs` in order.
+    ///
+    /// Fails if a regular expression is invalid or if a config lacks a
+    /// field required by its action, as Prometheus would.
+    pub fn new(configs: impl IntoIterator<Item = RelabelConfig>) -> Result<Self, Error> {
+        let rules = configs
+            .into_iter()
+            .map(|config| {
+                config.validate()?;
+
+                let regex = crate::re2::compile(&config.regex)?;
+
+                Ok(Rule { config, regex })
+            })
+            .collect::<Result<_, Error>>()?;
+
+        Ok(Self { rules })
+    }
+
+    /// Creates a relabeler from a YAML sequence of relabel configs, as
+    /// found under `relabel_configs` or `metric_relabel_configs` in the
+    /// configuration of Prometheus.
+    pub fn from_yaml(yaml: &str) -> Result<Self, Error> {
+        Self::new(serde_yaml::from_str::<Vec<RelabelConfig>>(yaml).map_err(Error::invalid_input)?)
+    }
+
+    /// Relabels `labels`, returning `None` if they were dropped.
+    ///
+    /// Labels set by the rules replace existing labels in place or are
+    /// appended, and setting a label to an empty value removes it.
+    pub fn relabel(&self, mut labels: Labels) -> Option<Labels> {
+        for rule in &self.rules {
+            if !rule.apply(&mut labels) {
+                return None;
+            }
+        }
+
+        Some(labels)
+    }
+
+    /// Serializes `label_set` as with [`to_string`](crate::to_string) and
+    /// relabels its labels, returning `None` if they were dropped.
+    pub fn relabel_label_set(&self, label_set: &impl Serialize) -> Result<Option<Labels>, Error> {
+        Ok(self.relabel(crate::top::pairs(label_set)?))
+    }
+}
+
+impl Rule {
+    /// Applies this rule to `labels`, returning `false` if they should be
+    /// dropped.
+    fn apply(&self, labels: &mut Labels) -> bool {
+        let config = &self.config;
+
+        match config.action {
+            Action::Replace => {
+                let value = self.source_value(labels);
+
+                if let Some(captures) = self.regex.captures(&value) {
+                    let target = expand(&captures, &config.target_label);
+
+                    if is_valid_label_name(&target) {
+                        set(
+                            labels,
+                            target.into(),
+                            expand(&captures, &config.replacement),
+                        );
+                    }
+                }
+            }
+            Action::Keep => return self.regex.is_match(&self.source_value(labels)),
+            Action::Drop => return !self.regex.is_match(&self.source_value(labels)),
+            Action::KeepEqual => return self.source_value(labels) == self.target_value(labels),
+            Action::DropEqual => return self.source_value(labels) != self.target_value(labels),
+            Action::HashMod => {
+                let hash = Md5::digest(self.source_value(labels).as_bytes());
+                let hash = u64::from_be_bytes(hash[8..].try_into().unwrap());
+
+                set(
+                    labels,
+                    config.target_label.clone().into(),
+                    (hash % config.modulus).to_string(),
+                );
+            }
+            Action::LabelMap => {
+                let mapped = labels
+                    .iter()
+                    .filter_map(|(name, value)| {
+                        let captures = self.regex.captures(name)?;
+                        let name = expand(&captures, &config.replacement);
+
+                        is_valid_label_name(&name).then(|| (name, value.clone()))
+                    })
+                    .collect::<Vec<_>>();
+
+                for (name, value) in mapped {
+                    set(labels, name.into(), value);
+                }
+            }
+            Action::LabelDrop => labels.retain(|(name, _)| !self.regex.is_match(name)),
+            Action::LabelKeep => labels.retain(|(name, _)| self.regex.is_match(name)),
+            Action::Lowercase => {
+                let value = self.source_value(labels).to_lowercase();
+
+                set(labels, config.target_label.clone().into(), value);
+            }
+            Action::Uppercase => {
+                let value = self.source_value(labels).to_uppercase();
+
+                set(labels, config.target_label.clone().into(), value);
+            }
+        }
+
+        true
+    }
+
+    /// Joins the values of the source labels with the separator, missing
+    /// labels having empty values.
+    fn source_value(&self, labels: &Labels) -> String {
+        let values = self.config.source_labels.iter().map(|source| {
+            labels
+                .iter()
+                .find(|(name, _)| name == source)
+                .map_or("", |(_, value)| value)
+        });
+
+        values.collect::<Vec<_>>().join(&self.config.separator)
+    }
+
+    /// Returns the value of the target label, missing labels having empty
+    /// values.
+    fn target_value<'a>(&self, labels: &'a Labels) -> &'a str {
+        labels
+            .iter()
+            .find(|(name, _)| *name == self.config.target_label)
+            .map_or("", |(_, value)| value)
+    }
+}
+
+/// A relabeling rule, with the same fields and defaults as the
+/// `relabel_config` sections of Prometheus.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RelabelConfig {
+    /// The labels whose values are joined with `separator` and matched
+    /// against `regex`.
+    pub source_labels: Vec<String>,
+    /// Defaults to `;`.
+    pub separator: String,
+    /// The label written by the `replace`, `hashmod`, `lowercase` and
+    /// `uppercase` actions, or compared by the `keepequal` and `dropequal`
+    /// actions.
+    pub target_label: String,
+    /// A fully anchored regular expression, defaulting to `(.*)`.
+    pub regex: String,
+    /// The modulus of the `hashmod` action.
+    pub modulus: u64,
+    /// The replacement of the `replace` and `labelmap` actions, in which
+    /// capture groups are referenced as `$1` or `${name}`. Defaults to `$1`.
+    pub replacement: String,
+    pub action: Action,
+}
+
+impl Default for RelabelConfig {
+    fn default() -> Self {
+        Self {
+            source_labels: vec![],
+            separator: ";".to_owned(),
+            target_label: "".to_owned(),
+            regex: "(.*)".to_owned(),
+            modulus: 0,
+            replacement: "$1".to_owned(),
+            action: Action::Replace,
+        }
+    }
+}
+
+impl RelabelConfig {
+    /// Performs the checks of Prometheus.
+    fn validate(&self) -> Result<(), Error> {
+        let action = self.action;
+        let default = Self::default();
+
+        let (requires_target, writes_target) = match action {
+            Action::Replace => (true, false),
+            Action::HashMod
+            | Action::Lowercase
+            | Action::Uppercase
+            | Action::KeepEqual
+            | Action::DropEqual => (true, true),
+            _ => (false, false),
+        };
+
+        if action == Action::HashMod && self.modulus == 0 {
+            return Err(invalid_config(action, "requires non-zero 'modulus' value"));
+        }
+
+        if requires_target && self.target_label.is_empty() {
+            return Err(invalid_config(action, "requires 'target_label' value"));
+        }
+
+        let valid_target = match action {
+            // The target label may reference capture groups.
+            Action::Replace if self.target_label.contains('$') => {
+                is_valid_template(&self.target_label)
+            }
+            Action::Replace => is_valid_label_name(&self.target_label),
+            _ => !writes_target || is_valid_label_name(&self.target_label),
+        };
+
+        if !valid_target {
+            return Err(invalid_config(
+                action,
+                "requires a valid 'target_label' value",
+            ));
+        }
+
+        match action {
+            Action::Lowercase | Action::Uppercase | Action::KeepEqual | Action::DropEqual
+                if self.replacement != default.replacement =>
+            {
+                Err(invalid_config(action, "doesn't allow 'replacement' value"))
+            }
+            Action::LabelMap if !is_valid_template(&self.replacement) => Err(invalid_config(
+                action,
+                "requires a valid 'replacement' value",
+            )),
+            Action::KeepEqual | Action::DropEqual
+                if self.regex != default.regex
+                    || self.modulus != default.modulus
+                    || self.separator != default.separator =>
+            {
+                Err(invalid_config(
+                    action,
+                    "requires only 'source_labels' and 'target_label', and no other fields",
+                ))
+            }
+            Action::LabelDrop | Action::LabelKeep
+                if !self.source_labels.is_empty()
+                    || self.target_label != default.target_label
+                    || self.modulus != default.modulus
+                    || self.separator != default.separator
+                    || self.replacement != default.replacement =>
+            {
+                Err(invalid_config(
+                    action,
+                    "requires only 'regex', and no other fields",
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The action of a [`RelabelConfig`].
+///
+/// As in Prometheus, action names are case-insensitive.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Action {
+    /// Sets `target_label` to `replacement` if `regex` matches.
+    #[default]
+    Replace,
+    /// Drops the label set if `regex` doesn't match.
+    Keep,
+    /// Drops the label set if `regex` matches.
+    Drop,
+    /// Drops the label set if the source value isn't equal to the value of
+    /// `target_label`.
+    KeepEqual,
+    /// Drops the label set if the source value is equal to the value of
+    /// `target_label`.
+    DropEqual,
+    /// Sets `target_label` to the MD5 hash of the source value modulo
+    /// `modulus`.
+    HashMod,
+    /// Copies the labels whose name matches `regex` to the labels named by
+    /// `replacement`.
+    LabelMap,
+    /// Removes the labels whose name matches `regex`.
+    LabelDrop,
+    /// Removes the labels whose name doesn't match `regex`.
+    LabelKeep,
+    /// Sets `target_label` to the lowercased source value.
+    Lowercase,
+    /// Sets `target_label` to the uppercased source value.
+    Uppercase,
+}
+
+impl<'de> Deserialize<'de> for Action {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        const VARIANTS: &[&str] = &[
+            "replace",
+            "keep",
+            "drop",
+            "keepequal",
+            "dropequal",
+            "hashmod",
+            "labelmap",
+            "labeldrop",
+            "labelkeep",
+            "lowercase",
+            "uppercase",
+        ];
+
+        let name = String::deserialize(deserializer)?;
+
+        Ok(match name.to_ascii_lowercase().as_str() {
+            "replace" => Action::Replace,
+            "keep" => Action::Keep,
+            "drop" => Action::Drop,
+            "keepequal" => Action::KeepEqual,
+            "dropequal" => Action::DropEqual,
+            "hashmod" => Action::HashMod,
+            "labelmap" => Action::LabelMap,
+            "labeldrop" => Action::LabelDrop,
+            "labelkeep" => Action::LabelKeep,
+            "lowercase" => Action::Lowercase,
+            "uppercase" => Action::Uppercase,
+            _ => return Err(de::Error::unknown_variant(&name, VARIANTS)),
+        })
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Action::Replace => "replace",
+            Action::Keep => "keep",
+            Action::Drop => "drop",
+            Action::KeepEqual => "keepequal",
+            Action::DropEqual => "dropequal",
+            Action::HashMod => "hashmod",
+            Action::LabelMap => "labelmap",
+            Action::LabelDrop => "labeldrop",
+            Action::LabelKeep => "labelkeep",
+            Action::Lowercase => "lowercase",
+            Action::Uppercase => "uppercase",
+        })
+    }
+}
+
+/// Writes `labels` in the format of [`to_string`](crate::to_string).
+#[cfg(feature = "bridge")]
+pub(crate) fn write_labels(buf: &mut Vec<u8>, labels: &Labels) -> Result<(), Error> {
+    use serde::Serializer;
+
+    for (i, (name, value)) in labels.iter().enumerate() {
+        if i > 0 {
+            buf.push(b',');
+        }
+
+        buf.extend_from_slice(name.as_bytes());
+        buf.extend_from_slice(b"=\"");
+        crate::value::serializer(crate::str::Writer::new(&mut *buf)).serialize_str(value)?;
+        buf.push(b'"');
+    }
+
+    Ok(())
+}
+
+/// Sets the label `name` to `value`, removing it if `value` is empty.
+fn set(labels: &mut Labels, name: Cow<'static, str>, value: String) {
+    let index = labels.iter().position(|(n, _)| *n == name);
+
+    match (index, value.is_empty()) {
+        (Some(index), true) => {
+            labels.remove(index);
+        }
+        (Some(index), false) => labels[index].1 = value,
+        (None, true) => {}
+        (None, false) => labels.push((name, value)),
+    }
+}
+
+fn expand(captures: &Captures<'_>, template: &str) -> String {
+    let mut expanded = String::new();
+    captures.expand(template, &mut expanded);
+
+    expanded
+}
+
+/// Returns whether `template` is a valid label name once its references to
+/// capture groups are expanded, like the `relabelTarget` regex of
+/// Prometheus.
+fn is_valid_template(template: &str) -> bool {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut rest = template;
+
+    if rest.starts_with(|c: char| c.is_ascii_digit()) {
+        return false;
+    }
+
+    while !rest.is_empty() {
+        rest = rest.trim_start_matches(is_word);
+
+        let Some(reference) = rest.strip_prefix('$') else {
+            return rest.is_empty();
+        };
+
+        rest = match reference.strip_prefix('{') {
+            Some(braced) => match braced.split_once('}') {
+                Some((name, after)) if !name.is_empty() && name.chars().all(is_word) => after,
+                _ => return false,
+            },
+            None if reference.starts_with(is_word) => reference,
+            None => return false,
+        };
+    }
+
+    !template.is_empty()
+}
+
+fn is_valid_label_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn invalid_config(action: Action, message: &'static str) -> Error {
+    #[derive(Debug)]
+    struct InvalidConfigError {
+        action: Action,
+        message: &'static str,
+    }
+
+    impl error::Error for InvalidConfigError {
+        #[allow(deprecated)]
+        fn description(&self) -> &str {
+            "invalid relabel config"
+        }
+    }
+
+    impl fmt::Display for InvalidConfigError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "invalid relabel config ({} action {})",
+                self.action, self.message,
+            )
+        }
+    }
+
+    Error::invalid_input(InvalidConfigError { action, message })
+}
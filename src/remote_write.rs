@@ -0,0 +1,150 @@
+//! Encoding of label sets into the messages of the Prometheus remote write
+//! protocol, as defined in `prometheus.WriteRequest`.
+//!
+//! #### Examples
+//!
+//! ```rust
+//! # use prost::Message;
+//! # use serde::Serialize;
+//! # use serde_prometheus_labels::remote_write::{self, Label, Sample, WriteRequest};
+//! #
+//! #[derive(Serialize)]
+//! struct Labels {
+//!     job: &'static str,
+//!     instance: &'static str,
+//! }
+//!
+//! let labels = Labels {
+//!     job: "backup",
+//!     instance: "unionize.org",
+//! };
+//! let samples = [Sample {
+//!     value: 42.0,
+//!     timestamp: 1_666_000_000_000,
+//! }];
+//!
+//! let request = remote_write::write_request([("backup_files", &labels, samples)]).unwrap();
+//! let body = request.to_compressed_bytes().unwrap();
+//!
+//! let decompressed = snap::raw::Decoder::new().decompress_vec(&body).unwrap();
+//! let decoded = WriteRequest::decode(&*decompressed).unwrap();
+//!
+//! assert_eq!(decoded, request);
+//! assert_eq!(
+//!     decoded.timeseries[0].labels,
+//!     [
+//!         Label::new("__name__", "backup_files"),
+//!         Label::new("instance", "unionize.org"),
+//!         Label::new("job", "backup"),
+//!     ],
+//! );
+//! ```
+
+use crate::Error;
+use prost::Message;
+use serde::Serialize;
+use std::io;
+
+/// Serializes `label_set` into the labels of a series named `name`.
+///
+/// The labels are sorted by name, as required by the protocol, and include
+/// the `__name__` label. Labels with empty values, such as `None` fields,
+/// are skipped, as Prometheus treats them as missing.
+///
+/// Fails if `label_set` already has a `__name__` label.
+pub fn labels(name: &str, label_set: &impl Serialize) -> Result<Vec<Label>, Error> {
+    let mut labels = crate::top::pairs(label_set)?;
+
+    labels.push(("__name__".into(), name.to_owned()));
+    labels.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    if let Some(window) = labels.windows(2).find(|window| window[0].0 == window[1].0) {
+        return Err(crate::top::duplicate_key(&window[0].0));
+    }
+
+    Ok(labels
+        .into_iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(name, value)| Label {
+            name: name.into_owned(),
+            value,
+        })
+        .collect())
+}
+
+/// Builds a request holding a time series for each
+/// `(metric name, label set, samples)` tuple of `series`.
+pub fn write_request<'a, S, I>(
+    series: impl IntoIterator<Item = (&'a str, &'a S, I)>,
+) -> Result<WriteRequest, Error>
+where
+    S: 'a + Serialize,
+    I: IntoIterator<Item = Sample>,
+{
+    let timeseries = series
+        .into_iter()
+        .map(|(name, label_set, samples)| {
+            Ok(TimeSeries {
+                labels: labels(name, label_set)?,
+                samples: samples.into_iter().collect(),
+            })
+        })
+        .collect::<Result<_, Error>>()?;
+
+    Ok(WriteRequest { timeseries })
+}
+
+/// The body of a remote write request.
+#[derive(Clone, PartialEq, Message)]
+pub struct WriteRequest {
+    #[prost(message, repeated, tag = "1")]
+    pub timeseries: Vec<TimeSeries>,
+}
+
+impl WriteRequest {
+    /// Encodes this request and compresses it with the block format of
+    /// Snappy, as expected by remote write receivers.
+    pub fn to_compressed_bytes(&self) -> Result<Vec<u8>, Error> {
+        snap::raw::Encoder::new()
+            .compress_vec(&self.encode_to_vec())
+            .map_err(|error| Error::new(io::Error::from(error)))
+    }
+}
+
+/// A series of a [`WriteRequest`].
+#[derive(Clone, PartialEq, Message)]
+pub struct TimeSeries {
+    /// The labels of the series, sorted by name.
+    #[prost(message, repeated, tag = "1")]
+    pub labels: Vec<Label>,
+    #[prost(message, repeated, tag = "2")]
+    pub samples: Vec<Sample>,
+}
+
+/// A label of a [`TimeSeries`].
+#[derive(Clone, PartialEq, Message)]
+pub struct Label {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub value: String,
+}
+
+impl Label {
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+}
+
+/// A sample of a [`TimeSeries`].
+#[derive(Clone, Copy, PartialEq, Message)]
+pub struct Sample {
+    #[prost(double, tag = "1")]
+    pub value: f64,
+    /// The timestamp of the sample, in milliseconds since the Unix epoch.
+    #[prost(int64, tag = "2")]
+    pub timestamp: i64,
+}
@@ -0,0 +1,276 @@
+use crate::error::{Error, Unexpected};
+use crate::str::Writer;
+use crate::top::Labels;
+use crate::value::{self, unexpected};
+use alloc::borrow::Cow;
+use alloc::string::String;
+use core::{error, fmt};
+use serde::ser::{Impossible, Serialize, Serializer};
+
+const NOT_EQUAL: &str = "$serde_prometheus_labels::NotEqual";
+const REGEX_MATCH: &str = "$serde_prometheus_labels::RegexMatch";
+const NOT_REGEX_MATCH: &str = "$serde_prometheus_labels::NotRegexMatch";
+
+/// Matches the label values which aren't equal to the wrapped value, when
+/// serialized with [`to_selector`](crate::to_selector).
+///
+/// It is serialized like the wrapped value by other functions of this crate.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct NotEqual<T>(pub T);
+
+/// Matches the label values which match the wrapped regular expression, when
+/// serialized with [`to_selector`](crate::to_selector).
+///
+/// It is serialized like the wrapped value by other functions of this crate.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct RegexMatch<T>(pub T);
+
+/// Matches the label values which don't match the wrapped regular
+/// expression, when serialized with [`to_selector`](crate::to_selector).
+///
+/// It is serialized like the wrapped value by other functions of this crate.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct NotRegexMatch<T>(pub T);
+
+macro_rules! matchers {
+    ($($ty:ident: $name:ident,)*) => {$(
+        impl<T> Serialize for $ty<T>
+        where
+            T: Serialize,
+        {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                serializer.serialize_newtype_struct($name, &self.0)
+            }
+        }
+    )*}
+}
+
+matchers! {
+    NotEqual: NOT_EQUAL,
+    RegexMatch: REGEX_MATCH,
+    NotRegexMatch: NOT_REGEX_MATCH,
+}
+
+/// Writes labels as comma-separated matchers.
+pub(crate) struct MatcherWriter<'a> {
+    matchers: &'a mut String,
+}
+
+impl<'a> MatcherWriter<'a> {
+    pub(crate) fn new(matchers: &'a mut String) -> Self {
+        Self { matchers }
+    }
+}
+
+impl Labels for MatcherWriter<'_> {
+    fn serialize_label<T>(&mut self, key: Cow<'static, str>, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let mut serialized = String::new();
+
+        let op = match value.serialize(MatcherSerializer {
+            value: &mut serialized,
+        })? {
+            Some(op) => op,
+            None => return Ok(()),
+        };
+
+        if !self.matchers.is_empty() {
+            self.matchers.push(',');
+        }
+
+        self.matchers.push_str(&key);
+        self.matchers.push_str(op);
+        self.matchers.push('"');
+        self.matchers.push_str(&serialized);
+        self.matchers.push('"');
+
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Serializes a label value into `value`, returning the operator of its
+/// matcher, or `None` if the label should be skipped.
+struct MatcherSerializer<'a> {
+    value: &'a mut String,
+}
+
+macro_rules! delegate {
+    ($($method:ident: $ty:ty,)*) => {$(
+        fn $method(self, v: $ty) -> Result<Self::Ok, Error> {
+            value::serializer(Writer::new(&mut *self.value)).$method(v)?;
+
+            Ok(Some("="))
+        }
+    )*}
+}
+
+impl Serializer for MatcherSerializer<'_> {
+    type Ok = Option<&'static str>;
+    type Error = Error;
+    type SerializeSeq = Impossible<Self::Ok, Error>;
+    type SerializeTuple = Impossible<Self::Ok, Error>;
+    type SerializeTupleStruct = Impossible<Self::Ok, Error>;
+    type SerializeTupleVariant = Impossible<Self::Ok, Error>;
+    type SerializeMap = Impossible<Self::Ok, Error>;
+    type SerializeStruct = Impossible<Self::Ok, Error>;
+    type SerializeStructVariant = Impossible<Self::Ok, Error>;
+
+    delegate! {
+        serialize_bool: bool,
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_i128: i128,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_u128: u128,
+        serialize_f32: f32,
+        serialize_f64: f64,
+        serialize_char: char,
+        serialize_str: &str,
+        serialize_bytes: &[u8],
+        serialize_unit_struct: &'static str,
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Error> {
+        Ok(Some("="))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        ty: &'static str,
+        index: u32,
+        name: &'static str,
+    ) -> Result<Self::Ok, Error> {
+        value::serializer(Writer::new(&mut *self.value)).serialize_unit_variant(ty, index, name)?;
+
+        Ok(Some("="))
+    }
+
+    fn serialize_newtype_struct<T>(self, ty: &'static str, value: &T) -> Result<Self::Ok, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let op = match ty {
+            NOT_EQUAL => "!=",
+            REGEX_MATCH => "=~",
+            NOT_REGEX_MATCH => "!~",
+            _ => return value.serialize(self),
+        };
+
+        Ok(value.serialize(self)?.map(|_| op))
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        ty: &'static str,
+        _index: u32,
+        name: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(unexpected(Unexpected::Variant(ty, name)))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(unexpected(Unexpected::Seq(len)))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(unexpected(Unexpected::Tuple(len)))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        ty: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(unexpected(Unexpected::Struct(ty)))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        ty: &'static str,
+        _index: u32,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(unexpected(Unexpected::Variant(ty, name)))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(unexpected(Unexpected::Map(len)))
+    }
+
+    fn serialize_struct(
+        self,
+        ty: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Err(unexpected(Unexpected::Struct(ty)))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        ty: &'static str,
+        _index: u32,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(unexpected(Unexpected::Variant(ty, name)))
+    }
+
+    fn collect_str<T>(self, value: &T) -> Result<Self::Ok, Error>
+    where
+        T: ?Sized + fmt::Display,
+    {
+        value::serializer(Writer::new(&mut *self.value)).collect_str(value)?;
+
+        Ok(Some("="))
+    }
+}
+
+pub(crate) fn empty_selector() -> Error {
+    #[derive(Debug)]
+    struct EmptySelectorError;
+
+    impl error::Error for EmptySelectorError {
+        #[allow(deprecated)]
+        fn description(&self) -> &str {
+            "empty selector"
+        }
+    }
+
+    impl fmt::Display for EmptySelectorError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("empty selector (a selector requires a name or a label matcher)")
+        }
+    }
+
+    Error::invalid_input(EmptySelectorError)
+}
@@ -1,37 +1,27 @@
-use std::{io, str};
+use crate::{Error, Output};
+use core::str;
 
 /// A writer to which you can only write slices, through `Self::write_str`.
-pub(crate) struct Writer<'io, W>
-where
-    W: ?Sized,
-{
-    inner: &'io mut W,
-}
-
-impl<'io> Writer<'io, Vec<u8>> {
-    pub(crate) fn from_mut_string(s: &'io mut String) -> Self {
-        // SAFETY: The only way to write to the IO sink is through methods
-        // that take strings, which are guaranteed to be valid UTF-8.
-        unsafe { Writer::new(s.as_mut_vec()) }
-    }
+pub(crate) struct Writer<O> {
+    inner: O,
 }
 
-impl<'io, W> Writer<'io, W>
+impl<O> Writer<O>
 where
-    W: ?Sized + io::Write,
+    O: Output,
 {
-    pub(crate) fn new(inner: &'io mut W) -> Self {
+    pub(crate) fn new(inner: O) -> Self {
         Self { inner }
     }
 
-    pub(crate) fn reborrow(&mut self) -> Writer<'_, W> {
+    pub(crate) fn reborrow(&mut self) -> Writer<&mut O> {
         Writer {
-            inner: &mut *self.inner,
+            inner: &mut self.inner,
         }
     }
 
-    pub(crate) fn write_str(&mut self, s: &str) -> io::Result<()> {
-        self.inner.write_all(s.as_bytes())
+    pub(crate) fn write_str(&mut self, s: &str) -> Result<(), Error> {
+        self.inner.write_str(s)
     }
 }
 
@@ -44,7 +34,7 @@ pub(crate) struct AsciiPattern {
 impl AsciiPattern {
     /// Will fail to compile in a const context if the chars aren't ASCII.
     pub(crate) const fn new(chars: &'static [u8]) -> Self {
-        #[allow(clippy::blocks_in_if_conditions)]
+        #[allow(clippy::blocks_in_conditions)]
         if {
             let mut i = 0;
             loop {
@@ -57,7 +47,7 @@ impl AsciiPattern {
             }
         } {
             #[allow(unconditional_panic)]
-            #[allow(clippy::no_effect)]
+            #[allow(clippy::no_effect, clippy::out_of_bounds_indexing)]
             ([] as [u8; 0])[0]; // Invalid ASCII chars
         }
 
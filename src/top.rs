@@ -1,27 +1,173 @@
 use crate::error::{Error, Unexpected};
 use crate::str::Writer;
 use crate::value;
-use serde::ser::{Impossible, Serialize, SerializeStruct, Serializer};
-use std::error;
-use std::fmt;
-use std::io::Write;
+use crate::{FmtOutput, Output};
+use alloc::borrow::{Cow, ToOwned};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::error;
+use core::fmt;
+use serde::ser::{Impossible, Serialize, SerializeMap, SerializeStruct, Serializer};
+
+pub(crate) struct TopSerializer<L> {
+    labels: L,
+}
+
+impl<L> TopSerializer<L>
+where
+    L: Labels,
+{
+    pub(crate) fn new(labels: L) -> Self {
+        Self { labels }
+    }
+}
+
+/// What [`TopSerializer`] does with the labels it finds.
+pub(crate) trait Labels {
+    /// Called for each label, after its key has been checked.
+    ///
+    /// Keys are borrowed for struct fields and owned for map entries.
+    fn serialize_label<T>(&mut self, key: Cow<'static, str>, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize;
 
-pub(crate) struct TopSerializer<'w, W>
+    fn end(self) -> Result<(), Error>;
+}
+
+/// Writes labels as comma-separated key-value pairs.
+pub(crate) struct LabelWriter<W> {
+    has_written_anything: bool,
+    writer: Writer<W>,
+}
+
+impl<W> LabelWriter<W>
 where
-    W: ?Sized,
+    W: Output,
 {
-    writer: Writer<'w, W>,
+    pub(crate) fn new(writer: Writer<W>) -> Self {
+        Self {
+            has_written_anything: false,
+            writer,
+        }
+    }
+}
+
+impl<W> Labels for LabelWriter<W>
+where
+    W: Output,
+{
+    fn serialize_label<T>(&mut self, key: Cow<'static, str>, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if self.has_written_anything {
+            self.writer.write_str("\",")?;
+        } else {
+            self.has_written_anything = true;
+        }
+
+        self.writer.write_str(&key)?;
+        self.writer.write_str("=\"")?;
+
+        value.serialize(value::serializer(self.writer.reborrow()))
+    }
+
+    fn end(mut self) -> Result<(), Error> {
+        if self.has_written_anything {
+            self.writer.write_str("\"")?;
+        }
+
+        Ok(())
+    }
 }
 
-impl<'w, W> TopSerializer<'w, W>
+/// Displays the labels of a value, as returned by [`crate::display`].
+pub(crate) struct DisplayLabels<'a, T>(pub(crate) &'a T)
+where
+    T: ?Sized;
+
+impl<T> fmt::Display for DisplayLabels<'_, T>
 where
-    W: ?Sized + Write,
+    T: ?Sized + Serialize,
 {
-    pub(crate) fn new(writer: Writer<'w, W>) -> Self {
-        Self { writer }
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0
+            .serialize(TopSerializer::new(LabelWriter::new(Writer::new(
+                FmtOutput(f),
+            ))))
+            .map_err(|_| fmt::Error)
     }
 }
 
+/// Writes the labels of `value` to `output`, failing if one of their keys
+/// is reserved.
+#[cfg(feature = "bridge")]
+pub(crate) fn write_unreserved(
+    output: impl Output,
+    value: &impl Serialize,
+    is_reserved: impl Fn(&str) -> bool,
+) -> Result<(), Error> {
+    struct Unreserved<L, F> {
+        labels: L,
+        is_reserved: F,
+    }
+
+    impl<L, F> Labels for Unreserved<L, F>
+    where
+        L: Labels,
+        F: Fn(&str) -> bool,
+    {
+        fn serialize_label<T>(&mut self, key: Cow<'static, str>, value: &T) -> Result<(), Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            if (self.is_reserved)(&key) {
+                return Err(duplicate_key(&key));
+            }
+
+            self.labels.serialize_label(key, value)
+        }
+
+        fn end(self) -> Result<(), Error> {
+            self.labels.end()
+        }
+    }
+
+    value.serialize(TopSerializer::new(Unreserved {
+        labels: LabelWriter::new(Writer::new(output)),
+        is_reserved,
+    }))
+}
+
+/// Collects the labels of `value` as key-value pairs, without escaping
+/// their values.
+pub(crate) fn pairs(value: &impl Serialize) -> Result<Vec<(Cow<'static, str>, String)>, Error> {
+    struct Pairs<'a>(&'a mut Vec<(Cow<'static, str>, String)>);
+
+    impl Labels for Pairs<'_> {
+        fn serialize_label<T>(&mut self, key: Cow<'static, str>, value: &T) -> Result<(), Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            let mut string = String::new();
+            value.serialize(value::raw_serializer(Writer::new(&mut string)))?;
+            self.0.push((key, string));
+
+            Ok(())
+        }
+
+        fn end(self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    let mut pairs = vec![];
+    value.serialize(TopSerializer::new(Pairs(&mut pairs)))?;
+
+    Ok(pairs)
+}
+
 macro_rules! unsupported_scalars {
     ($($($method:ident: $kind:ident($ty:ty)),+ $(,)?)?) => {$($(
         #[inline]
@@ -31,9 +177,9 @@ macro_rules! unsupported_scalars {
     )+)?}
 }
 
-impl<'w, W> Serializer for TopSerializer<'w, W>
+impl<L> Serializer for TopSerializer<L>
 where
-    W: ?Sized + Write,
+    L: Labels,
 {
     type Ok = ();
     type Error = Error;
@@ -41,8 +187,8 @@ where
     type SerializeTuple = Impossible<(), Error>;
     type SerializeTupleStruct = Impossible<(), Error>;
     type SerializeTupleVariant = Impossible<(), Error>;
-    type SerializeMap = Impossible<(), Error>;
-    type SerializeStruct = StructSerializer<'w, W>;
+    type SerializeMap = MapSerializer<L>;
+    type SerializeStruct = StructSerializer<L>;
     type SerializeStructVariant = Impossible<(), Error>;
 
     unsupported_scalars! {
@@ -156,8 +302,11 @@ where
     }
 
     #[inline]
-    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Error> {
-        Err(unsupported(Unexpected::Map(len)))
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Ok(MapSerializer {
+            labels: self.labels,
+            key: None,
+        })
     }
 
     #[inline]
@@ -167,8 +316,7 @@ where
         _len: usize,
     ) -> Result<Self::SerializeStruct, Error> {
         Ok(StructSerializer {
-            has_written_anything: false,
-            writer: self.writer,
+            labels: self.labels,
         })
     }
 
@@ -184,17 +332,13 @@ where
     }
 }
 
-pub(crate) struct StructSerializer<'w, W>
-where
-    W: ?Sized,
-{
-    has_written_anything: bool,
-    writer: Writer<'w, W>,
+pub(crate) struct StructSerializer<L> {
+    labels: L,
 }
 
-impl<W> SerializeStruct for StructSerializer<'_, W>
+impl<L> SerializeStruct for StructSerializer<L>
 where
-    W: ?Sized + Write,
+    L: Labels,
 {
     type Ok = ();
     type Error = Error;
@@ -205,29 +349,93 @@ where
     {
         check_key(key)?;
 
-        if self.has_written_anything {
-            self.writer.write_str("\",").map_err(Error::new)?;
-        } else {
-            self.has_written_anything = true;
-        }
+        self.labels.serialize_label(Cow::Borrowed(key), value)
+    }
+
+    #[inline]
+    fn end(self) -> Result<(), Error> {
+        self.labels.end()
+    }
+}
 
-        self.writer.write_str(key).map_err(Error::new)?;
-        self.writer.write_str("=\"").map_err(Error::new)?;
+/// Serializes the entries of a map as labels, with keys serialized like
+/// label values.
+pub(crate) struct MapSerializer<L> {
+    labels: L,
+    key: Option<String>,
+}
 
-        value.serialize(value::serializer(self.writer.reborrow()))
+impl<L> SerializeMap for MapSerializer<L>
+where
+    L: Labels,
+{
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let mut string = String::new();
+        key.serialize(value::raw_serializer(Writer::new(&mut string)))?;
+        check_key(&string)?;
+
+        self.key = Some(string);
+
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = self
+            .key
+            .take()
+            .expect("serialize_value called before serialize_key");
+
+        self.labels.serialize_label(Cow::Owned(key), value)
     }
 
     #[inline]
-    fn end(mut self) -> Result<(), Error> {
-        if self.has_written_anything {
-            self.writer.write_str("\"").map_err(Error::new)?;
+    fn end(self) -> Result<(), Error> {
+        self.labels.end()
+    }
+}
+
+pub(crate) fn check_metric_name(name: &str) -> Result<(), Error> {
+    let mut chars = name.chars();
+
+    if chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+    {
+        return Ok(());
+    }
+
+    #[derive(Debug)]
+    struct InvalidMetricNameError(String);
+
+    impl error::Error for InvalidMetricNameError {
+        #[allow(deprecated)]
+        fn description(&self) -> &str {
+            "invalid metric name"
         }
+    }
 
-        Ok(())
+    impl fmt::Display for InvalidMetricNameError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "invalid metric name ({:?})", self.0)
+        }
     }
+
+    Err(Error::invalid_input(InvalidMetricNameError(
+        name.to_owned(),
+    )))
 }
 
-fn check_key(key: &'static str) -> Result<(), Error> {
+pub(crate) fn check_key(key: &str) -> Result<(), Error> {
     let mut chars = key.chars();
 
     chars
@@ -241,9 +449,9 @@ fn check_key(key: &'static str) -> Result<(), Error> {
         .ok_or_else(|| invalid_key(key))
 }
 
-fn invalid_key(key: &'static str) -> Error {
+fn invalid_key(key: &str) -> Error {
     #[derive(Debug)]
-    struct InvalidKeyError(&'static str);
+    struct InvalidKeyError(String);
 
     impl error::Error for InvalidKeyError {
         #[allow(deprecated)]
@@ -258,7 +466,27 @@ fn invalid_key(key: &'static str) -> Error {
         }
     }
 
-    Error::invalid_input(InvalidKeyError(key))
+    Error::invalid_input(InvalidKeyError(key.to_owned()))
+}
+
+pub(crate) fn duplicate_key(key: &str) -> Error {
+    #[derive(Debug)]
+    struct DuplicateKeyError(String);
+
+    impl error::Error for DuplicateKeyError {
+        #[allow(deprecated)]
+        fn description(&self) -> &str {
+            "duplicate key"
+        }
+    }
+
+    impl fmt::Display for DuplicateKeyError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "duplicate key ({:?})", self.0)
+        }
+    }
+
+    Error::invalid_input(DuplicateKeyError(key.to_owned()))
 }
 
 fn unsupported(kind: Unexpected) -> Error {
@@ -1,21 +1,35 @@
 use crate::error::{Error, Unexpected};
 use crate::str::{AsciiPattern, Writer};
+use crate::Output;
+use core::{error, fmt, str};
 use serde::ser::{Impossible, Serialize, Serializer};
-use std::{error, fmt, io, str};
 
 #[inline]
-pub(crate) fn serializer<W>(writer: Writer<'_, W>) -> impl '_ + Serializer<Ok = (), Error = Error>
+pub(crate) fn serializer<W>(writer: Writer<W>) -> impl Serializer<Ok = (), Error = Error>
 where
-    W: ?Sized + io::Write,
+    W: Output,
 {
-    ValueSerializer { writer }
+    ValueSerializer {
+        writer,
+        escape: true,
+    }
 }
 
-struct ValueSerializer<'w, W>
+/// Like [`serializer`], but doesn't escape anything.
+#[inline]
+pub(crate) fn raw_serializer<W>(writer: Writer<W>) -> impl Serializer<Ok = (), Error = Error>
 where
-    W: ?Sized,
+    W: Output,
 {
-    writer: Writer<'w, W>,
+    ValueSerializer {
+        writer,
+        escape: false,
+    }
+}
+
+struct ValueSerializer<W> {
+    writer: Writer<W>,
+    escape: bool,
 }
 
 macro_rules! delegate {
@@ -27,9 +41,9 @@ macro_rules! delegate {
     )?)*)*}
 }
 
-impl<'w, W> Serializer for ValueSerializer<'w, W>
+impl<W> Serializer for ValueSerializer<W>
 where
-    W: ?Sized + io::Write,
+    W: Output,
 {
     type Ok = ();
     type Error = Error;
@@ -70,6 +84,10 @@ where
     }
 
     fn serialize_char(mut self, v: char) -> Result<Self::Ok, Error> {
+        if !self.escape {
+            return self.write_unchecked(v.encode_utf8(&mut [0; 4]));
+        }
+
         self.write_unchecked(match v {
             '"' => r#"\""#,
             '\\' => r#"\\"#,
@@ -84,11 +102,11 @@ where
     }
 
     fn serialize_str(mut self, value: &str) -> Result<Self::Ok, Error> {
-        write_escaped(self.writer.reborrow(), value).map_err(Error::new)
+        self.write_str(value)
     }
 
     fn serialize_bytes(self, _value: &[u8]) -> Result<Self::Ok, Error> {
-        Err(self.unexpected(Unexpected::Bytes))
+        Err(unexpected(Unexpected::Bytes))
     }
 
     fn serialize_unit(self) -> Result<Self::Ok, Error> {
@@ -121,7 +139,7 @@ where
     where
         T: ?Sized + Serialize,
     {
-        Err(self.unexpected(Unexpected::Variant(ty, name)))
+        Err(unexpected(Unexpected::Variant(ty, name)))
     }
 
     fn serialize_none(self) -> Result<Self::Ok, Error> {
@@ -136,11 +154,11 @@ where
     }
 
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
-        Err(self.unexpected(Unexpected::Seq(len)))
+        Err(unexpected(Unexpected::Seq(len)))
     }
 
     fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Error> {
-        Err(self.unexpected(Unexpected::Tuple(len)))
+        Err(unexpected(Unexpected::Tuple(len)))
     }
 
     fn serialize_tuple_struct(
@@ -148,7 +166,7 @@ where
         ty: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTuple, Error> {
-        Err(self.unexpected(Unexpected::Struct(ty)))
+        Err(unexpected(Unexpected::Struct(ty)))
     }
 
     fn serialize_tuple_variant(
@@ -158,11 +176,11 @@ where
         name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant, Error> {
-        Err(self.unexpected(Unexpected::Variant(ty, name)))
+        Err(unexpected(Unexpected::Variant(ty, name)))
     }
 
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Error> {
-        Err(self.unexpected(Unexpected::Map(len)))
+        Err(unexpected(Unexpected::Map(len)))
     }
 
     fn serialize_struct(
@@ -170,7 +188,7 @@ where
         ty: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStruct, Error> {
-        Err(self.unexpected(Unexpected::Struct(ty)))
+        Err(unexpected(Unexpected::Struct(ty)))
     }
 
     fn serialize_struct_variant(
@@ -180,30 +198,27 @@ where
         name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant, Error> {
-        Err(self.unexpected(Unexpected::Variant(ty, name)))
+        Err(unexpected(Unexpected::Variant(ty, name)))
     }
 
     fn collect_str<T>(mut self, value: &T) -> Result<Self::Ok, Error>
     where
         T: ?Sized + fmt::Display,
     {
-        struct Adapter<'w, W>
-        where
-            W: ?Sized,
-        {
-            writer: Writer<'w, W>,
+        struct Adapter<'s, W> {
+            serializer: &'s mut ValueSerializer<W>,
             error: Option<Error>,
         }
 
-        impl<'w, W> fmt::Write for Adapter<'w, W>
+        impl<W> fmt::Write for Adapter<'_, W>
         where
-            W: ?Sized + io::Write,
+            W: Output,
         {
             fn write_str(&mut self, s: &str) -> fmt::Result {
                 debug_assert!(self.error.is_none());
 
-                write_escaped(self.writer.reborrow(), s).map_err(|err| {
-                    self.error = Some(Error::new(err));
+                self.serializer.write_str(s).map_err(|err| {
+                    self.error = Some(err);
 
                     fmt::Error
                 })
@@ -211,7 +226,7 @@ where
         }
 
         let mut adapter = Adapter {
-            writer: self.writer.reborrow(),
+            serializer: &mut self,
             error: None,
         };
 
@@ -230,9 +245,9 @@ where
     }
 }
 
-impl<'w, W> ValueSerializer<'w, W>
+impl<W> ValueSerializer<W>
 where
-    W: ?Sized + io::Write,
+    W: Output,
 {
     fn serialize_integer<I>(mut self, value: I) -> Result<(), Error>
     where
@@ -254,35 +269,40 @@ where
         self.write_unchecked(part)
     }
 
+    fn write_str(&mut self, s: &str) -> Result<(), Error> {
+        if self.escape {
+            write_escaped(self.writer.reborrow(), s)
+        } else {
+            self.write_unchecked(s)
+        }
+    }
+
     fn write_unchecked(&mut self, raw: &str) -> Result<(), Error> {
-        self.writer.write_str(raw).map_err(Error::new)
+        self.writer.write_str(raw)
     }
+}
 
-    fn unexpected(&self, kind: Unexpected) -> Error {
-        #[derive(Debug)]
-        struct UnexpectedValueError(Unexpected);
+pub(crate) fn unexpected(kind: Unexpected) -> Error {
+    #[derive(Debug)]
+    struct UnexpectedValueError(Unexpected);
 
-        impl error::Error for UnexpectedValueError {
-            #[allow(deprecated)]
-            fn description(&self) -> &str {
-                "unexpected value"
-            }
+    impl error::Error for UnexpectedValueError {
+        #[allow(deprecated)]
+        fn description(&self) -> &str {
+            "unexpected value"
         }
+    }
 
-        impl fmt::Display for UnexpectedValueError {
-            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-                write!(f, "unexpected {}", self.0)
-            }
+    impl fmt::Display for UnexpectedValueError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "unexpected {}", self.0)
         }
-
-        Error::invalid_input(UnexpectedValueError(kind))
     }
+
+    Error::invalid_input(UnexpectedValueError(kind))
 }
 
-fn write_escaped(
-    mut writer: Writer<'_, impl ?Sized + io::Write>,
-    mut s: &str,
-) -> Result<(), io::Error> {
+fn write_escaped(mut writer: Writer<impl Output>, mut s: &str) -> Result<(), Error> {
     const PATTERN: AsciiPattern = AsciiPattern::new(b"\"\\\n");
 
     while let Some((chunk, found)) = PATTERN.take_until_match(&mut s) {
@@ -297,7 +317,7 @@ fn write_escaped(
 
             // SAFETY: We know that `found` is an ASCII char, so `escape_buf`
             // contains valid UTF-8.
-            unsafe { std::str::from_utf8_unchecked(&escape_buf) }
+            unsafe { str::from_utf8_unchecked(&escape_buf) }
         })?;
     }
 
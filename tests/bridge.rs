@@ -0,0 +1,561 @@
+#![cfg(feature = "bridge")]
+
+use prometheus_client::{
+    encoding::text::encode,
+    metrics::{counter::Counter, gauge::Gauge},
+    registry::Registry,
+};
+use serde::Serialize;
+use serde_prometheus_labels::{
+    Collector, CounterWithExemplar, Family, FamilyGroup, Info, ShardedFamily, TextfileWriter,
+};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Eq, Hash, PartialEq, Serialize)]
+struct Labels {
+    path: Path,
+}
+
+#[derive(Clone, Eq, Hash, PartialEq, Serialize)]
+enum Path {
+    #[serde(rename = "/metrics")]
+    Metrics,
+    Other(String),
+}
+
+#[test]
+fn skip_invalid_series() {
+    let errors = Counter::default();
+    let reported = Arc::new(Mutex::new(vec![]));
+    let family = <Family<Labels, Counter>>::default().skip_invalid_series(errors.clone(), {
+        let reported = reported.clone();
+        move |labels, error| {
+            reported
+                .lock()
+                .unwrap()
+                .push((labels.clone(), error.to_string()));
+        }
+    });
+    let mut registry = <Registry>::default();
+
+    registry.register("requests", "Number of requests", Box::new(family.clone()));
+    registry.register("label_errors", "Number of invalid series", Box::new(errors));
+
+    family
+        .get_or_create(&Labels {
+            path: Path::Metrics,
+        })
+        .inc();
+    family
+        .get_or_create(&Labels {
+            path: Path::Other("/".to_string()),
+        })
+        .inc();
+
+    let mut serialized = vec![];
+    encode(&mut serialized, &registry).unwrap();
+
+    assert_eq!(
+        String::from_utf8(serialized).unwrap(),
+        concat!(
+            "# HELP requests Number of requests.\n",
+            "# TYPE requests counter\n",
+            "requests_total{path=\"/metrics\"} 1\n",
+            "# HELP label_errors Number of invalid series.\n",
+            "# TYPE label_errors counter\n",
+            "label_errors_total 1\n",
+            "# EOF\n",
+        ),
+    );
+
+    let reported = reported.lock().unwrap();
+
+    assert_eq!(reported.len(), 1);
+    assert!(
+        reported[0].0
+            == Labels {
+                path: Path::Other("/".to_string())
+            }
+    );
+    assert_eq!(reported[0].1, "unexpected variant Path::Other");
+}
+
+#[test]
+fn skip_invalid_series_reported_once() {
+    let errors = Counter::default();
+    let reported = Arc::new(Mutex::new(0));
+    let group =
+        <FamilyGroup<Labels, (Counter, Counter)>>::default().skip_invalid_series(errors.clone(), {
+            let reported = reported.clone();
+            move |_, _| *reported.lock().unwrap() += 1
+        });
+    let mut registry = <Registry>::default();
+
+    registry.register("requests", "Number of requests", Box::new(group.member(0)));
+    registry.register("failures", "Number of failures", Box::new(group.member(1)));
+
+    group
+        .get_or_create(&Labels {
+            path: Path::Other("/".to_string()),
+        })
+        .0
+        .inc();
+
+    for _ in 0..3 {
+        encode(&mut vec![], &registry).unwrap();
+    }
+
+    // Each member skips the series at each scrape.
+    assert_eq!(errors.get(), 6);
+    assert_eq!(*reported.lock().unwrap(), 1);
+}
+
+#[test]
+fn const_labels_duplicate_key() {
+    #[derive(Serialize)]
+    struct ConstLabels {
+        path: &'static str,
+    }
+
+    let family = <Family<Labels, Counter>>::default()
+        .with_const_labels(&ConstLabels { path: "/" })
+        .unwrap();
+    let mut registry = Registry::default();
+
+    registry.register("requests", "Number of requests", family.clone());
+
+    family
+        .get_or_create(&Labels {
+            path: Path::Metrics,
+        })
+        .inc();
+
+    let err = encode(&mut vec![], &registry).unwrap_err();
+
+    assert_eq!(err.to_string(), r#"duplicate key ("path")"#);
+}
+
+#[test]
+fn const_labels_skip_duplicate_key() {
+    #[derive(Serialize)]
+    struct ConstLabels {
+        path: &'static str,
+    }
+
+    let errors = Counter::default();
+    let family = <Family<BTreeMap<&str, &str>, Counter>>::default()
+        .with_const_labels(&ConstLabels { path: "/" })
+        .unwrap()
+        .skip_invalid_series(errors.clone(), |_, _| {});
+    let mut registry = Registry::default();
+
+    registry.register("requests", "Number of requests", family.clone());
+
+    family
+        .get_or_create(&BTreeMap::from([("path", "/metrics")]))
+        .inc();
+    family
+        .get_or_create(&BTreeMap::from([("host", "unionize.org")]))
+        .inc();
+
+    let mut serialized = vec![];
+    encode(&mut serialized, &registry).unwrap();
+
+    assert_eq!(
+        String::from_utf8(serialized).unwrap(),
+        concat!(
+            "# HELP requests Number of requests.\n",
+            "# TYPE requests counter\n",
+            "requests_total{path=\"/\",host=\"unionize.org\"} 1\n",
+            "# EOF\n",
+        ),
+    );
+    assert_eq!(errors.get(), 1);
+}
+
+#[test]
+fn sharded_family() {
+    #[derive(Clone, Eq, Hash, PartialEq, Serialize)]
+    struct Labels {
+        id: u32,
+    }
+
+    let family = <ShardedFamily<Labels, Counter>>::new(8);
+    let mut registry = Registry::default();
+
+    registry.register("requests", "Number of requests", family.clone());
+
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                for id in 0..100 {
+                    family.get_or_create(&Labels { id }).inc();
+                }
+            });
+        }
+    });
+
+    let mut serialized = vec![];
+    encode(&mut serialized, &registry).unwrap();
+
+    let serialized = String::from_utf8(serialized).unwrap();
+    let mut lines = serialized
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect::<Vec<_>>();
+
+    lines.sort_by_key(|line| line[line.find('"').unwrap() + 1..].to_owned());
+
+    assert_eq!(lines.len(), 100);
+    assert_eq!(lines[0], r#"requests_total{id="0"} 4"#);
+    assert_eq!(lines[99], r#"requests_total{id="99"} 4"#);
+}
+
+#[test]
+fn sort_by() {
+    #[derive(Clone, Eq, Hash, PartialEq, Serialize)]
+    struct Labels {
+        id: u32,
+    }
+
+    let family = <ShardedFamily<Labels, Counter>>::new(8).sort_by(|a, b| b.id.cmp(&a.id));
+    let mut registry = Registry::default();
+
+    registry.register("requests", "Number of requests", family.clone());
+
+    for id in [2, 10, 1] {
+        family.get_or_create(&Labels { id }).inc();
+    }
+
+    let mut serialized = vec![];
+    encode(&mut serialized, &registry).unwrap();
+
+    assert_eq!(
+        String::from_utf8(serialized).unwrap(),
+        concat!(
+            "# HELP requests Number of requests.\n",
+            "# TYPE requests counter\n",
+            "requests_total{id=\"10\"} 1\n",
+            "requests_total{id=\"2\"} 1\n",
+            "requests_total{id=\"1\"} 1\n",
+            "# EOF\n",
+        ),
+    );
+}
+
+#[test]
+#[should_panic(expected = "metric index 2 out of bounds for a group of 2 metrics")]
+fn group_member_out_of_bounds() {
+    <FamilyGroup<Labels, (Counter, Counter)>>::default().member(2);
+}
+
+#[test]
+fn counter_collector() {
+    #[derive(Serialize)]
+    struct Backend {
+        backend: &'static str,
+    }
+
+    let mut registry = Registry::default();
+
+    registry.register(
+        "pool_acquired_seconds",
+        "Time spent acquiring connections",
+        Collector::counter(|| [(Backend { backend: "primary" }, 1.5)]),
+    );
+
+    let mut serialized = vec![];
+    encode(&mut serialized, &registry).unwrap();
+
+    assert_eq!(
+        String::from_utf8(serialized).unwrap(),
+        concat!(
+            "# HELP pool_acquired_seconds Time spent acquiring connections.\n",
+            "# TYPE pool_acquired_seconds counter\n",
+            "pool_acquired_seconds_total{backend=\"primary\"} 1.5\n",
+            "# EOF\n",
+        ),
+    );
+}
+
+#[test]
+fn collector_options() {
+    #[derive(Serialize)]
+    struct ConstLabels {
+        region: &'static str,
+    }
+
+    let errors = Counter::default();
+    let mut registry = Registry::default();
+
+    registry.register(
+        "queue_depth",
+        "Number of queued jobs",
+        Collector::gauge(|| {
+            [
+                BTreeMap::from([("tenant", "unionize.org"), ("the queue", "mail")]),
+                BTreeMap::from([("tenant", "amazon.com")]),
+            ]
+            .into_iter()
+            .map(|labels| (labels, 3u64))
+        })
+        .with_const_labels(&ConstLabels { region: "eu" })
+        .unwrap()
+        .skip_invalid_series(errors.clone(), |_, _| {}),
+    );
+
+    let mut serialized = vec![];
+    encode(&mut serialized, &registry).unwrap();
+
+    assert_eq!(
+        String::from_utf8(serialized).unwrap(),
+        concat!(
+            "# HELP queue_depth Number of queued jobs.\n",
+            "# TYPE queue_depth gauge\n",
+            "queue_depth{region=\"eu\",tenant=\"amazon.com\"} 3\n",
+            "# EOF\n",
+        ),
+    );
+    assert_eq!(errors.get(), 1);
+}
+
+#[test]
+fn exponential_histogram() {
+    let family = <Family<Labels, _, _>>::exponential_histogram(0.25, 4.0, 3);
+    let mut registry = Registry::default();
+
+    registry.register("latency", "Latency of requests", family.clone());
+
+    family
+        .get_or_create(&Labels {
+            path: Path::Metrics,
+        })
+        .observe(3.0);
+
+    let mut serialized = vec![];
+    encode(&mut serialized, &registry).unwrap();
+
+    assert_eq!(
+        String::from_utf8(serialized).unwrap(),
+        concat!(
+            "# HELP latency Latency of requests.\n",
+            "# TYPE latency histogram\n",
+            "latency_sum{path=\"/metrics\"} 3.0\n",
+            "latency_count{path=\"/metrics\"} 1\n",
+            "latency_bucket{path=\"/metrics\",le=\"0.25\"} 0\n",
+            "latency_bucket{path=\"/metrics\",le=\"1.0\"} 0\n",
+            "latency_bucket{path=\"/metrics\",le=\"4.0\"} 1\n",
+            "latency_bucket{path=\"/metrics\",le=\"+Inf\"} 1\n",
+            "# EOF\n",
+        ),
+    );
+}
+
+#[test]
+fn exemplar_too_long() {
+    #[derive(Serialize)]
+    struct Trace {
+        trace_id: String,
+    }
+
+    let counter = <CounterWithExemplar<Trace>>::default();
+
+    let error = counter
+        .inc_by(
+            1,
+            Some(Trace {
+                trace_id: "é".repeat(121),
+            }),
+        )
+        .unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "exemplar too long (129 chars, at most 128 allowed)",
+    );
+    assert_eq!(counter.get(), 0);
+
+    counter
+        .inc_by(
+            1,
+            Some(Trace {
+                trace_id: "é".repeat(120),
+            }),
+        )
+        .unwrap();
+
+    assert_eq!(counter.get(), 1);
+}
+
+#[test]
+fn textfile_consistency() {
+    let dir = std::env::temp_dir().join(format!("textfile-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    #[derive(Clone, Eq, Hash, PartialEq, Serialize)]
+    struct Labels {
+        job: &'static str,
+    }
+
+    let writer = TextfileWriter::new(&dir);
+    let family = <Family<Labels, Counter>>::default();
+
+    family.get_or_create(&Labels { job: "backup" }).inc();
+
+    writer
+        .write_metrics("a.prom", [("requests", "Number of requests", &family as _)])
+        .unwrap();
+
+    let error = writer
+        .write_metrics("b.prom", [("requests", "Requests", &family as _)])
+        .unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "inconsistent help for metric family \"requests_total\" in {}",
+            dir.join("a.prom").display(),
+        ),
+    );
+
+    let error = writer
+        .write_metrics("b.prom", [("requests", "Number of requests", &family as _)])
+        .unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "duplicate series for metric family \"requests_total\" in {}",
+            dir.join("a.prom").display(),
+        ),
+    );
+
+    let other = <Family<Labels, Counter>>::default();
+
+    other.get_or_create(&Labels { job: "cleanup" }).inc();
+
+    writer
+        .write_metrics("b.prom", [("requests", "Number of requests", &other as _)])
+        .unwrap();
+
+    // Rewriting a file isn't checked against its previous contents.
+    writer
+        .write_metrics("a.prom", [("requests", "Number of requests", &family as _)])
+        .unwrap();
+
+    assert_eq!(
+        std::fs::read_to_string(dir.join("a.prom")).unwrap(),
+        concat!(
+            "# HELP requests_total Number of requests.\n",
+            "# TYPE requests_total counter\n",
+            "requests_total{job=\"backup\"} 1\n",
+        ),
+    );
+
+    // Samples are only grouped with the family of a preceding `TYPE` line
+    // if their name is the family name with a known suffix.
+    std::fs::write(
+        dir.join("c.prom"),
+        concat!(
+            "# HELP http Whether HTTP is up.\n",
+            "# TYPE http gauge\n",
+            "http{job=\"backup\"} 1\n",
+            "http_requests_total{path=\"/\"} 1\n",
+        ),
+    )
+    .unwrap();
+
+    let http = <Family<Labels, Gauge>>::default();
+
+    http.get_or_create(&Labels { job: "cleanup" }).set(1);
+
+    writer
+        .write_metrics("d.prom", [("http", "Whether HTTP is up", &http as _)])
+        .unwrap();
+
+    std::fs::remove_file(dir.join("c.prom")).unwrap();
+    std::fs::remove_file(dir.join("d.prom")).unwrap();
+
+    let mut files = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    files.sort();
+
+    assert_eq!(files, ["a.prom", "b.prom"]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn info() {
+    #[derive(Serialize)]
+    struct BuildInfo {
+        version: &'static str,
+        git_sha: Option<&'static str>,
+    }
+
+    let mut registry = Registry::default();
+
+    registry.register(
+        "build_info",
+        "Build information",
+        Info::new(BuildInfo {
+            version: "0.2.0",
+            git_sha: None,
+        }),
+    );
+
+    let mut serialized = vec![];
+    encode(&mut serialized, &registry).unwrap();
+
+    assert_eq!(
+        String::from_utf8(serialized).unwrap(),
+        concat!(
+            "# HELP build_info Build information.\n",
+            "# TYPE build_info gauge\n",
+            "build_info{version=\"0.2.0\",git_sha=\"\"} 1\n",
+            "# EOF\n",
+        ),
+    );
+
+    let mut registry = Registry::default();
+
+    registry.register(
+        "build_info",
+        "Build information",
+        Info::new(BTreeMap::from([("git sha", "f44b9bb")])),
+    );
+
+    let err = encode(&mut vec![], &registry).unwrap_err();
+
+    assert_eq!(err.to_string(), r#"invalid key ("git sha")"#);
+}
+
+#[test]
+fn get_or_create_owned() {
+    let family = <Family<Labels, Counter>>::default();
+    let labels = Labels {
+        path: Path::Metrics,
+    };
+
+    let requests = family.get_or_create_owned(&labels);
+
+    // The handle doesn't hold any lock, so the family can still create series.
+    family
+        .get_or_create(&Labels {
+            path: Path::Other("/".to_string()),
+        })
+        .inc();
+    requests.inc();
+
+    assert!(Arc::ptr_eq(&requests, &family.get_or_create_owned(&labels)));
+    assert_eq!(family.get_or_create(&labels).get(), 1);
+
+    let sharded = <ShardedFamily<Labels, Counter>>::new(4);
+
+    sharded.get_or_create_owned(&labels).inc();
+
+    assert_eq!(sharded.get_or_create(&labels).get(), 1);
+}
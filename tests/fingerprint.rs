@@ -0,0 +1,73 @@
+#![cfg(feature = "fingerprint")]
+
+use serde::Serialize;
+use serde_prometheus_labels::fingerprint;
+use std::collections::BTreeMap;
+
+#[test]
+fn fingerprints() {
+    #[derive(Serialize)]
+    struct Labels {
+        path: &'static str,
+        reason: Option<&'static str>,
+        host: &'static str,
+        method: &'static str,
+    }
+
+    #[derive(Serialize)]
+    struct Duplicate {
+        a: u8,
+        #[serde(rename = "a")]
+        b: u8,
+    }
+
+    // The xxHash64 of empty input with seed 0, from the sanity checks of the
+    // xxHash reference implementation, which is what `EmptyLabels().Hash()`
+    // returns in Prometheus.
+    assert_eq!(fingerprint(&()).unwrap(), 0xef46db3751d8e999);
+    assert_eq!(
+        fingerprint(&BTreeMap::from([("a", "")])).unwrap(),
+        0xef46db3751d8e999,
+    );
+
+    // Labels with empty values aren't hashed.
+    assert_eq!(
+        fingerprint(&Labels {
+            path: "/metrics",
+            reason: None,
+            host: "",
+            method: "GET",
+        })
+        .unwrap(),
+        fingerprint(&BTreeMap::from([("method", "GET"), ("path", "/metrics")])).unwrap(),
+    );
+
+    assert_eq!(
+        fingerprint(&Duplicate { a: 1, b: 2 })
+            .unwrap_err()
+            .to_string(),
+        "duplicate key (\"a\")",
+    );
+}
+
+#[test]
+fn long_fingerprints() {
+    let long = (0..64)
+        .map(|i| (format!("label_{i:02}"), "v".repeat(i + 1)))
+        .collect::<BTreeMap<_, _>>();
+    let bytes = long
+        .iter()
+        .flat_map(|(name, value)| [name.as_bytes(), b"\xff", value.as_bytes(), b"\xff"])
+        .flatten()
+        .copied()
+        .collect::<Vec<_>>();
+
+    assert!(bytes.len() > 1024);
+
+    // `labels.Hash` streams label sets of 1 KiB or more into a digest, which
+    // gives the hash of the whole bytes.
+    assert_eq!(
+        fingerprint(&long).unwrap(),
+        xxhash_rust::xxh64::xxh64(&bytes, 0),
+    );
+}
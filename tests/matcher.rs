@@ -0,0 +1,112 @@
+#![cfg(feature = "matcher")]
+
+use serde::Serialize;
+use serde_prometheus_labels::matcher::{MatchOp, Matcher, Selector};
+
+#[derive(Serialize)]
+struct Labels {
+    env: &'static str,
+    shard: Option<u32>,
+}
+
+#[test]
+fn parse_and_display() {
+    let selector = r#" http_requests_total { env =~ 'prod|st\x61ging' , path!=`C:\tmp`, } "#
+        .parse::<Selector>()
+        .unwrap();
+
+    assert_eq!(
+        selector.matchers(),
+        [
+            Matcher::new("__name__", MatchOp::Equal, "http_requests_total").unwrap(),
+            Matcher::new("env", MatchOp::RegexMatch, "prod|staging").unwrap(),
+            Matcher::new("path", MatchOp::NotEqual, r"C:\tmp").unwrap(),
+        ],
+    );
+    assert_eq!(
+        selector.to_string(),
+        r#"http_requests_total{env=~"prod|staging",path!="C:\\tmp"}"#,
+    );
+    assert_eq!(selector.to_string().parse::<Selector>().unwrap(), selector);
+
+    for (input, error) in [
+        (
+            "{}",
+            "invalid selector (selector must contain at least one non-empty matcher)",
+        ),
+        (
+            r#"{env=~".*"}"#,
+            "invalid selector (selector must contain at least one non-empty matcher)",
+        ),
+        (
+            r#"{env="prod""#,
+            "invalid selector (expected `,` or `}` after matcher)",
+        ),
+        (
+            r#"{env=="prod"}"#,
+            "invalid selector (expected string literal)",
+        ),
+        (
+            r#"{env="\q"}"#,
+            "invalid selector (invalid escape sequence)",
+        ),
+        (
+            r#"up{env="prod"} 1"#,
+            "invalid selector (trailing characters)",
+        ),
+    ] {
+        assert_eq!(input.parse::<Selector>().unwrap_err().to_string(), error);
+    }
+}
+
+#[test]
+fn anchored_regex() {
+    let selector = r#"{env=~"prod|staging",shard!~"1.*"}"#.parse::<Selector>().unwrap();
+
+    let matches = |env, shard| selector.matches(&Labels { env, shard }).unwrap();
+
+    assert!(matches("prod", None));
+    assert!(matches("staging", Some(2)));
+    assert!(!matches("production", None));
+    assert!(!matches("preprod", None));
+    assert!(!matches("prod", Some(12)));
+
+    let multiline = Matcher::new("env", MatchOp::RegexMatch, "a.b").unwrap();
+
+    assert!(multiline.matches("a\nb"));
+    assert!(!multiline.matches("a\nbc"));
+}
+
+#[test]
+fn ascii_classes() {
+    let matches = |regex: &str, value: &str| {
+        Matcher::new("env", MatchOp::RegexMatch, regex)
+            .unwrap()
+            .matches(value)
+    };
+
+    // Like in RE2, Perl classes and word boundaries are ASCII-only.
+    assert!(matches(r"\d+", "123"));
+    assert!(!matches(r"\d", "٣"));
+    assert!(matches(r"\D", "٣"));
+    assert!(matches(r"[\d.]+", "1.5"));
+    assert!(!matches(r"[\d.]+", "١.٥"));
+    assert!(!matches(r"\w+", "café"));
+    assert!(!matches(r"\s", "\u{a0}"));
+    assert!(!matches(r".*\bé", "caf é"));
+    assert!(matches(r".*\Bé", "caf é"));
+    assert!(matches(r"\\d", "\\d"));
+}
+
+#[test]
+fn matches_series() {
+    let selector = r#"up{env="prod"}"#.parse::<Selector>().unwrap();
+    let labels = Labels {
+        env: "prod",
+        shard: None,
+    };
+
+    assert!(selector.matches_series("up", &labels).unwrap());
+    assert!(!selector.matches_series("down", &labels).unwrap());
+    assert!(!selector.matches(&labels).unwrap());
+}
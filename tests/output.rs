@@ -0,0 +1,44 @@
+#![cfg(feature = "bytes")]
+
+use bytes::BytesMut;
+use serde::Serialize;
+use serde_prometheus_labels::{to_output, BufMutOutput};
+
+#[derive(Serialize)]
+struct Labels {
+    method: &'static str,
+    path: &'static str,
+}
+
+const LABELS: Labels = Labels {
+    method: "GET",
+    path: "/\"metrics\"",
+};
+
+#[test]
+fn bytes_mut() {
+    let mut buf = BytesMut::new();
+    to_output(&mut buf, &LABELS).unwrap();
+
+    assert_eq!(&buf[..], br#"method="GET",path="/\"metrics\"""#);
+}
+
+#[test]
+fn buf_mut() {
+    let mut buf = [0; 64];
+    let mut output = BufMutOutput(&mut buf[..]);
+    to_output(&mut output, &LABELS).unwrap();
+    let remaining = output.0.len();
+
+    assert_eq!(
+        &buf[..64 - remaining],
+        br#"method="GET",path="/\"metrics\"""#
+    );
+}
+
+#[test]
+fn buf_mut_overflow() {
+    let mut buf = [0; 16];
+
+    assert!(to_output(BufMutOutput(&mut buf[..]), &LABELS).is_err());
+}
@@ -0,0 +1,131 @@
+#![cfg(feature = "protobuf")]
+
+use serde::Serialize;
+use serde_prometheus_labels::protobuf::{self, Bucket, LabelPair, MetricType};
+use serde_prometheus_labels::{CounterWithExemplar, Family};
+
+#[derive(Clone, Eq, Hash, PartialEq, Serialize)]
+struct Labels {
+    path: &'static str,
+}
+
+#[derive(Serialize)]
+struct ConstLabels {
+    region: &'static str,
+}
+
+fn label_pair(name: &str, value: &str) -> LabelPair {
+    LabelPair {
+        name: Some(name.to_owned()),
+        value: Some(value.to_owned()),
+    }
+}
+
+#[test]
+fn histogram() {
+    let family = <Family<Labels, _, _>>::histogram([0.5, 1.0])
+        .with_const_labels(&ConstLabels {
+            region: "eu-west-3",
+        })
+        .unwrap()
+        .sort_by_labels();
+
+    family
+        .get_or_create(&Labels { path: "/metrics" })
+        .observe(0.75);
+    family.get_or_create(&Labels { path: "/" }).observe(2.0);
+
+    let metric_family = protobuf::encode("latency", "Latency of requests", &family).unwrap();
+
+    assert_eq!(metric_family.name(), "latency");
+    assert_eq!(metric_family.help(), "Latency of requests");
+    assert_eq!(metric_family.r#type(), MetricType::Histogram);
+    assert_eq!(metric_family.metric.len(), 2);
+
+    let histogram = metric_family.metric[0].histogram.as_ref().unwrap();
+
+    // Observations above every bound only count in the implied +Inf bucket.
+    assert_eq!(histogram.sample_count(), 1);
+    assert_eq!(histogram.sample_sum(), 2.0);
+    assert_eq!(
+        histogram
+            .bucket
+            .iter()
+            .map(|bucket| bucket.cumulative_count())
+            .collect::<Vec<_>>(),
+        [0, 0],
+    );
+
+    let metric = &metric_family.metric[1];
+
+    assert_eq!(
+        metric.label,
+        [
+            label_pair("region", "eu-west-3"),
+            label_pair("path", "/metrics"),
+        ],
+    );
+
+    let histogram = metric.histogram.as_ref().unwrap();
+
+    assert_eq!(histogram.sample_count(), 1);
+    assert_eq!(histogram.sample_sum(), 0.75);
+    assert_eq!(
+        histogram.bucket,
+        [
+            Bucket {
+                cumulative_count: Some(0),
+                upper_bound: Some(0.5),
+                ..Default::default()
+            },
+            Bucket {
+                cumulative_count: Some(1),
+                upper_bound: Some(1.0),
+                ..Default::default()
+            },
+        ],
+    );
+}
+
+#[test]
+fn counter_with_exemplar() {
+    #[derive(Serialize)]
+    struct Trace {
+        trace_id: &'static str,
+    }
+
+    let family = <Family<Labels, CounterWithExemplar<Trace>>>::default();
+
+    family
+        .get_or_create(&Labels { path: "/metrics" })
+        .inc_by(2, Some(Trace { trace_id: "3c5d3f" }))
+        .unwrap();
+
+    let metric_family = protobuf::encode("requests", "Number of requests", &family).unwrap();
+
+    assert_eq!(metric_family.name(), "requests_total");
+    assert_eq!(metric_family.r#type(), MetricType::Counter);
+
+    let counter = metric_family.metric[0].counter.as_ref().unwrap();
+
+    assert_eq!(counter.value(), 2.0);
+    assert_eq!(counter.exemplar, None);
+}
+
+#[test]
+fn const_labels_duplicate_key() {
+    #[derive(Serialize)]
+    struct ConstLabels {
+        path: &'static str,
+    }
+
+    let family = <Family<Labels, prometheus_client::metrics::counter::Counter>>::default()
+        .with_const_labels(&ConstLabels { path: "/" })
+        .unwrap();
+
+    family.get_or_create(&Labels { path: "/metrics" }).inc();
+
+    let error = protobuf::encode("requests", "Number of requests", &family).unwrap_err();
+
+    assert_eq!(error.to_string(), "duplicate key (\"path\")");
+}
@@ -0,0 +1,299 @@
+#![cfg(feature = "relabel")]
+
+use serde::Serialize;
+use serde_prometheus_labels::relabel::{Action, RelabelConfig, Relabeler};
+
+#[derive(Serialize)]
+struct Labels {
+    host: &'static str,
+    env: &'static str,
+}
+
+const LABELS: Labels = Labels {
+    host: "unionize.org",
+    env: "Prod",
+};
+
+fn relabel(yaml: &str) -> Option<Vec<(String, String)>> {
+    Relabeler::from_yaml(yaml)
+        .unwrap()
+        .relabel_label_set(&LABELS)
+        .unwrap()
+        .map(|labels| {
+            labels
+                .into_iter()
+                .map(|(name, value)| (name.into_owned(), value))
+                .collect()
+        })
+}
+
+fn labels<const N: usize>(pairs: [(&str, &str); N]) -> Option<Vec<(String, String)>> {
+    Some(
+        pairs
+            .into_iter()
+            .map(|(name, value)| (name.to_owned(), value.to_owned()))
+            .collect(),
+    )
+}
+
+#[test]
+fn actions() {
+    assert_eq!(
+        relabel(
+            r#"
+- source_labels: [host, env]
+  regex: "(.*)\\.org;(.*)"
+  target_label: site
+  replacement: "${1}_$2"
+- source_labels: [env]
+  target_label: host
+  replacement: ""
+"#
+        ),
+        labels([("env", "Prod"), ("site", "unionize_Prod")]),
+    );
+
+    assert_eq!(
+        relabel("- {action: keep, source_labels: [env], regex: prod}"),
+        None,
+    );
+    assert_eq!(
+        relabel("- {action: drop, source_labels: [env], regex: prod}"),
+        labels([("host", "unionize.org"), ("env", "Prod")]),
+    );
+    assert_eq!(
+        relabel(
+            r#"
+- {action: labelmap, regex: "(h.*)", replacement: "the_$1"}
+- {action: labelkeep, regex: "the_.*|env"}
+- {action: lowercase, source_labels: [env], target_label: env}
+"#
+        ),
+        labels([("env", "prod"), ("the_host", "unionize.org")]),
+    );
+    assert_eq!(
+        relabel(
+            r#"
+- {action: hashmod, source_labels: [host], modulus: 8, target_label: shard}
+- {action: hashmod, source_labels: [host, env, missing], modulus: 1000, target_label: host}
+- {action: labeldrop, regex: env}
+"#
+        ),
+        labels([("host", "325"), ("shard", "5")]),
+    );
+}
+
+#[test]
+fn equal_actions() {
+    assert_eq!(
+        relabel(
+            r#"
+- {source_labels: [env], target_label: other, replacement: Prod}
+- {action: keepequal, source_labels: [other], target_label: env}
+"#
+        ),
+        labels([("host", "unionize.org"), ("env", "Prod"), ("other", "Prod")]),
+    );
+    assert_eq!(
+        relabel("- {action: keepequal, source_labels: [host], target_label: env}"),
+        None,
+    );
+    assert_eq!(
+        relabel("- {action: dropequal, source_labels: [missing], target_label: other}"),
+        None,
+    );
+    assert_eq!(
+        relabel("- {action: dropequal, source_labels: [host], target_label: env}"),
+        labels([("host", "unionize.org"), ("env", "Prod")]),
+    );
+}
+
+#[test]
+fn case_insensitive_actions() {
+    assert_eq!(
+        relabel(
+            r#"
+- {action: HashMod, source_labels: [host], modulus: 8, target_label: shard}
+- {action: Replace, source_labels: [env], target_label: env, replacement: staging}
+- {action: LABELDROP, regex: host}
+"#
+        ),
+        labels([("env", "staging"), ("shard", "5")]),
+    );
+    assert_eq!(
+        relabel("- {action: Drop, source_labels: [env], regex: Prod}"),
+        None,
+    );
+}
+
+#[test]
+fn ascii_classes() {
+    let relabeler =
+        Relabeler::from_yaml(r#"- {action: keep, source_labels: [shard], regex: '\d+'}"#).unwrap();
+
+    #[derive(Serialize)]
+    struct Shard {
+        shard: &'static str,
+    }
+
+    assert!(relabeler
+        .relabel_label_set(&Shard { shard: "12" })
+        .unwrap()
+        .is_some());
+    assert!(relabeler
+        .relabel_label_set(&Shard { shard: "١٢" })
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn invalid_configs() {
+    for (yaml, error) in [
+        ("- {target_label: a, regex: '('}", "regex parse error"),
+        (
+            "- {source_labels: [a]}",
+            "invalid relabel config (replace action requires 'target_label' value)",
+        ),
+        (
+            "- {action: hashmod, target_label: a}",
+            "invalid relabel config (hashmod action requires non-zero 'modulus' value)",
+        ),
+        (
+            "- {action: labeldrop, regex: a, target_label: b}",
+            "invalid relabel config (labeldrop action requires only 'regex', and no other fields)",
+        ),
+        (
+            "- {action: labelkeep, regex: a, separator: ','}",
+            "invalid relabel config (labelkeep action requires only 'regex', and no other fields)",
+        ),
+        (
+            "- {action: labeldrop, regex: a, replacement: b}",
+            "invalid relabel config (labeldrop action requires only 'regex', and no other fields)",
+        ),
+        (
+            "- {source_labels: [a], target_label: 1a}",
+            "invalid relabel config (replace action requires a valid 'target_label' value)",
+        ),
+        (
+            "- {source_labels: [a], target_label: 'a-$1'}",
+            "invalid relabel config (replace action requires a valid 'target_label' value)",
+        ),
+        (
+            "- {action: lowercase, source_labels: [a], target_label: 'a_$1'}",
+            "invalid relabel config (lowercase action requires a valid 'target_label' value)",
+        ),
+        (
+            "- {action: uppercase, source_labels: [a], target_label: a, replacement: b}",
+            "invalid relabel config (uppercase action doesn't allow 'replacement' value)",
+        ),
+        (
+            "- {action: labelmap, regex: '(.*)', replacement: 'a.$1'}",
+            "invalid relabel config (labelmap action requires a valid 'replacement' value)",
+        ),
+        (
+            "- {action: keepequal, source_labels: [a]}",
+            "invalid relabel config (keepequal action requires 'target_label' value)",
+        ),
+        (
+            "- {action: dropequal, source_labels: [a], target_label: b, regex: c}",
+            "invalid relabel config (dropequal action requires only 'source_labels' and 'target_label', and no other fields)",
+        ),
+        ("- {action: rename}", "unknown variant `rename`"),
+        ("- {target: a}", "unknown field `target`"),
+    ] {
+        let message = Relabeler::from_yaml(yaml).unwrap_err().to_string();
+
+        assert!(message.contains(error), "{message:?} for {yaml:?}");
+    }
+
+    let relabeler = Relabeler::new([RelabelConfig {
+        action: Action::Uppercase,
+        source_labels: vec!["env".to_owned()],
+        target_label: "env".to_owned(),
+        ..Default::default()
+    }])
+    .unwrap();
+
+    assert_eq!(
+        relabeler.relabel_label_set(&LABELS).unwrap().unwrap()[1].1,
+        "PROD",
+    );
+}
+
+#[test]
+fn target_label_templates() {
+    assert_eq!(
+        relabel("- {source_labels: [env], regex: '(P)(.*)', target_label: '${1}_$2'}"),
+        labels([("host", "unionize.org"), ("env", "Prod"), ("P_rod", "P")]),
+    );
+}
+
+#[cfg(feature = "bridge")]
+#[test]
+fn duplicate_series() {
+    use prometheus_client::encoding::text::encode;
+    use prometheus_client::metrics::counter::Counter;
+    use prometheus_client::registry::Registry;
+    use serde_prometheus_labels::Family;
+
+    #[derive(Clone, Eq, Hash, PartialEq, Serialize)]
+    struct Labels {
+        host: &'static str,
+        user_id: u64,
+    }
+
+    let family = |sorted: bool| {
+        let family = <Family<Labels, Counter>>::default()
+            .with_relabeler(Relabeler::from_yaml("- {action: labeldrop, regex: user_id}").unwrap());
+        let family = if sorted {
+            family.sort_by_labels()
+        } else {
+            family
+        };
+
+        for user_id in [1, 2] {
+            family.get_or_create(&Labels { host: "a", user_id }).inc();
+        }
+
+        family
+    };
+
+    for sorted in [false, true] {
+        let mut registry = <Registry>::default();
+
+        registry.register("requests", "Number of requests", Box::new(family(sorted)));
+
+        let error = encode(&mut vec![], &registry).unwrap_err();
+
+        assert_eq!(error.to_string(), r#"duplicate series ({host="a"})"#);
+
+        let errors = Counter::default();
+        let mut registry = <Registry>::default();
+        let family = family(sorted).skip_invalid_series(errors.clone(), |_, _| {});
+
+        registry.register("requests", "Number of requests", Box::new(family.clone()));
+
+        let mut serialized = vec![];
+        encode(&mut serialized, &registry).unwrap();
+
+        assert_eq!(
+            String::from_utf8(serialized).unwrap(),
+            concat!(
+                "# HELP requests Number of requests.\n",
+                "# TYPE requests counter\n",
+                "requests_total{host=\"a\"} 1\n",
+                "# EOF\n",
+            ),
+        );
+        assert_eq!(errors.get(), 1);
+
+        #[cfg(feature = "protobuf")]
+        {
+            let metric_family =
+                serde_prometheus_labels::protobuf::encode("requests", "", &family).unwrap();
+
+            assert_eq!(metric_family.metric.len(), 1);
+            assert_eq!(errors.get(), 2);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+#![cfg(feature = "remote-write")]
+
+use prost::Message;
+use serde::Serialize;
+use serde_prometheus_labels::remote_write::{self, Label, Sample, WriteRequest};
+
+#[test]
+fn unescaped_values() {
+    #[derive(Serialize)]
+    struct Labels {
+        query: &'static str,
+    }
+
+    let labels = remote_write::labels(
+        "queries",
+        &Labels {
+            query: "say \"hi\"\n",
+        },
+    )
+    .unwrap();
+
+    assert_eq!(
+        labels,
+        [
+            Label::new("__name__", "queries"),
+            Label::new("query", "say \"hi\"\n"),
+        ],
+    );
+}
+
+#[test]
+fn name_label() {
+    #[derive(Serialize)]
+    struct Labels {
+        __name__: &'static str,
+    }
+
+    let error = remote_write::labels("queries", &Labels { __name__: "jobs" }).unwrap_err();
+
+    assert_eq!(error.to_string(), "duplicate key (\"__name__\")");
+}
+
+#[test]
+fn empty_values() {
+    #[derive(Serialize)]
+    struct Labels {
+        job: &'static str,
+        instance: Option<&'static str>,
+        shard: &'static str,
+    }
+
+    let labels = remote_write::labels(
+        "up",
+        &Labels {
+            job: "backup",
+            instance: None,
+            shard: "",
+        },
+    )
+    .unwrap();
+
+    assert_eq!(
+        labels,
+        [Label::new("__name__", "up"), Label::new("job", "backup")],
+    );
+}
+
+#[test]
+fn compressed_request() {
+    #[derive(Serialize)]
+    struct Labels {
+        job: &'static str,
+    }
+
+    let samples = [
+        Sample {
+            value: 1.0,
+            timestamp: 1_666_000_000_000,
+        },
+        Sample {
+            value: 0.0,
+            timestamp: 1_666_000_015_000,
+        },
+    ];
+
+    let request = remote_write::write_request([
+        ("up", &Labels { job: "backup" }, samples.to_vec()),
+        ("up", &Labels { job: "cleanup" }, vec![]),
+    ])
+    .unwrap();
+    let body = request.to_compressed_bytes().unwrap();
+
+    let decompressed = snap::raw::Decoder::new().decompress_vec(&body).unwrap();
+    let decoded = WriteRequest::decode(&*decompressed).unwrap();
+
+    assert_eq!(decoded.timeseries.len(), 2);
+    assert_eq!(
+        decoded.timeseries[0].labels,
+        [Label::new("__name__", "up"), Label::new("job", "backup")],
+    );
+    assert_eq!(decoded.timeseries[0].samples, samples);
+    assert_eq!(
+        decoded.timeseries[1].labels,
+        [Label::new("__name__", "up"), Label::new("job", "cleanup")],
+    );
+    assert!(decoded.timeseries[1].samples.is_empty());
+}
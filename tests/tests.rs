@@ -1,5 +1,11 @@
-use serde::Serialize;
-use serde_prometheus_labels::to_string;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use serde_prometheus_labels::exposition::{MetricType, Parser, Writer};
+use serde_prometheus_labels::{
+    display, from_str, to_label_set, to_output, to_pushgateway_path, to_selector, to_string,
+    FmtOutput, LabelSet, NotEqual, NotRegexMatch,
+};
+use std::collections::BTreeMap;
 
 #[test]
 fn invalid_key() {
@@ -31,3 +37,365 @@ fn escapes() {
 
     assert_eq!(serialized, r#"field="slash: \\, newline: \n, quote: \"""#)
 }
+
+#[test]
+fn pushgateway_path() {
+    #[derive(Serialize)]
+    struct Labels {
+        query: &'static str,
+        host: &'static str,
+    }
+
+    let path = to_pushgateway_path(
+        "a b",
+        &Labels {
+            query: ">>?",
+            host: "unionize.org",
+        },
+    )
+    .unwrap();
+
+    assert_eq!(
+        path,
+        "/metrics/job@base64/YSBi/query@base64/Pj4_/host/unionize.org",
+    );
+
+    #[derive(Serialize)]
+    struct Job {
+        job: &'static str,
+    }
+
+    assert_eq!(
+        to_pushgateway_path("backup", &Job { job: "backup" })
+            .unwrap_err()
+            .to_string(),
+        "duplicate key (\"job\")",
+    );
+
+    assert_eq!(
+        to_pushgateway_path("", &BTreeMap::<&str, &str>::new())
+            .unwrap_err()
+            .to_string(),
+        "empty job (the Pushgateway requires a job name)",
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn exposition_prometheus_format() {
+    let mut writer = Writer::new(vec![]);
+
+    writer.help("temperature", "Temperature in \"C\".").unwrap();
+    writer
+        .metric_type("temperature", MetricType::Unknown)
+        .unwrap();
+    writer
+        .sample("temperature", &(), f64::NEG_INFINITY, Some(-1))
+        .unwrap();
+
+    assert_eq!(
+        writer
+            .metric_type("build", MetricType::Info)
+            .unwrap_err()
+            .to_string(),
+        "metric type unsupported by the Prometheus text format (Info)",
+    );
+    assert_eq!(
+        writer.sample("1up", &(), 1, None).unwrap_err().to_string(),
+        r#"invalid metric name ("1up")"#,
+    );
+
+    assert_eq!(
+        String::from_utf8(writer.finish().unwrap()).unwrap(),
+        concat!(
+            "# HELP temperature Temperature in \"C\".\n",
+            "# TYPE temperature untyped\n",
+            "temperature -Inf -1\n",
+        ),
+    );
+}
+
+#[test]
+fn from_str_roundtrip() {
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Labels {
+        query: String,
+        shard: Option<u32>,
+        cached: bool,
+    }
+
+    let labels = Labels {
+        query: "say \"hi\"\\\n".to_owned(),
+        shard: Some(3),
+        cached: false,
+    };
+
+    assert_eq!(
+        from_str::<Labels>(&to_string(&labels).unwrap()).unwrap(),
+        labels
+    );
+
+    assert_eq!(
+        from_str::<Labels>(r#"query="\t""#).unwrap_err().to_string(),
+        "invalid label set (invalid escape sequence)",
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn exposition_parser() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Labels {
+        host: Option<String>,
+    }
+
+    let text = concat!(
+        "# HELP up Whether the \"target\" is up.\\nOr \\\\not.\n",
+        "# TYPE up untyped\n",
+        "\n",
+        "up 1 1666000000123\n",
+        "up{host=\"unionize.org\",} NaN\n",
+        "up{host=\"unionize.org\"}\n",
+    );
+
+    let mut parser = Parser::<Labels>::new(text);
+
+    assert_eq!(
+        parser.next().unwrap().unwrap(),
+        ("up", Labels { host: None }, 1.0, Some(1_666_000_000_123)),
+    );
+    assert_eq!(
+        parser.help("up"),
+        Some("Whether the \"target\" is up.\nOr \\not.")
+    );
+    assert_eq!(parser.metric_type("up"), Some(MetricType::Unknown));
+
+    let (_, labels, value, timestamp) = parser.next().unwrap().unwrap();
+
+    assert_eq!(labels.host.as_deref(), Some("unionize.org"));
+    assert!(value.is_nan());
+    assert_eq!(timestamp, None);
+
+    assert_eq!(
+        parser.next().unwrap().unwrap_err().to_string(),
+        r#"invalid exposition line "up{host=\"unionize.org\"}" (missing value)"#,
+    );
+    assert!(parser.next().is_none());
+
+    let mut parser = Parser::<Labels>::open_metrics("up 1\n");
+
+    assert!(parser.next().unwrap().is_ok());
+    assert_eq!(
+        parser.next().unwrap().unwrap_err().to_string(),
+        r#"invalid exposition line "" (missing `# EOF`)"#,
+    );
+    assert!(parser.next().is_none());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn exposition_parser_blanks() {
+    let text = concat!(
+        "#  TYPE\tup  gauge \n",
+        "up{host=\"unionize.org\"}  1\n",
+        "up\t0\t1666000000123 \n",
+        "  up {host=\"amazon.com\"}\t\t1\n",
+    );
+
+    let mut parser = Parser::<BTreeMap<String, String>>::new(text);
+
+    let (name, labels, value, timestamp) = parser.next().unwrap().unwrap();
+
+    assert_eq!(parser.metric_type("up"), Some(MetricType::Gauge));
+    assert_eq!((name, value, timestamp), ("up", 1.0, None));
+    assert_eq!(labels["host"], "unionize.org");
+
+    let (_, labels, value, timestamp) = parser.next().unwrap().unwrap();
+
+    assert!(labels.is_empty());
+    assert_eq!((value, timestamp), (0.0, Some(1_666_000_000_123)));
+
+    let (_, labels, value, _) = parser.next().unwrap().unwrap();
+
+    assert_eq!(labels["host"], "amazon.com");
+    assert_eq!(value, 1.0);
+    assert!(parser.next().is_none());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn exposition_parser_help_escapes() {
+    let text = "# HELP up Whether the \\\"target\\\" is up.\n";
+
+    assert_eq!(
+        Parser::<()>::new(text)
+            .next()
+            .unwrap()
+            .unwrap_err()
+            .to_string(),
+        r##"invalid exposition line "# HELP up Whether the \\\"target\\\" is up." (invalid escape sequence in help)"##,
+    );
+
+    let mut parser = Parser::<()>::open_metrics(concat!(
+        "# HELP up Whether the \\\"target\\\" is up.\n",
+        "# EOF\n",
+    ));
+
+    assert!(parser.next().is_none());
+    assert_eq!(parser.help("up"), Some("Whether the \"target\" is up."));
+}
+
+#[test]
+fn selector() {
+    #[derive(Serialize)]
+    struct Labels {
+        path: NotRegexMatch<&'static str>,
+        reason: Option<NotEqual<&'static str>>,
+        shard: Option<u32>,
+    }
+
+    let labels = Labels {
+        path: NotRegexMatch("\"quoted\"\n"),
+        reason: Some(NotEqual("")),
+        shard: None,
+    };
+
+    assert_eq!(
+        to_selector("", &labels).unwrap(),
+        r#"{path!~"\"quoted\"\n",reason!=""}"#,
+    );
+    assert_eq!(
+        to_string(&labels).unwrap(),
+        r#"path="\"quoted\"\n",reason="",shard="""#,
+    );
+
+    #[derive(Serialize)]
+    struct Empty {
+        shard: Option<u32>,
+    }
+
+    assert_eq!(to_selector("up", &Empty { shard: None }).unwrap(), "up");
+    assert_eq!(
+        to_selector("", &Empty { shard: None })
+            .unwrap_err()
+            .to_string(),
+        "empty selector (a selector requires a name or a label matcher)",
+    );
+    assert_eq!(
+        to_selector("1up", &Empty { shard: None })
+            .unwrap_err()
+            .to_string(),
+        "invalid metric name (\"1up\")",
+    );
+}
+
+#[test]
+fn label_sets() {
+    let map = BTreeMap::from([("region", "eu-west-3"), ("host", "unionize.org")]);
+
+    assert_eq!(
+        to_string(&map).unwrap(),
+        r#"host="unionize.org",region="eu-west-3""#,
+    );
+    assert_eq!(
+        to_string(&BTreeMap::from([("the host", "unionize.org")]))
+            .unwrap_err()
+            .to_string(),
+        "invalid key (\"the host\")",
+    );
+
+    let mut label_set = to_label_set(&map).unwrap();
+    let other = r#"version="0.2.0",region="us-east-1""#.parse::<LabelSet>().unwrap();
+
+    assert_eq!(
+        label_set.merge(&other).unwrap_err().to_string(),
+        "duplicate key (\"region\")",
+    );
+    assert_eq!(label_set.len(), 2);
+
+    label_set.override_with(&other);
+
+    assert_eq!(
+        label_set.to_string(),
+        r#"host="unionize.org",region="us-east-1",version="0.2.0""#,
+    );
+    assert_eq!(
+        label_set.insert("host", "amazon.com").unwrap().as_deref(),
+        Some("unionize.org"),
+    );
+    assert!(label_set.insert("0host", "amazon.com").is_err());
+    assert_eq!(label_set.remove("version").as_deref(), Some("0.2.0"));
+    assert_eq!(label_set.get("host"), Some("amazon.com"));
+    assert_eq!(
+        label_set.iter().collect::<Vec<_>>(),
+        [("host", "amazon.com"), ("region", "us-east-1")],
+    );
+    assert!(label_set > LabelSet::new());
+    assert_eq!(
+        to_pushgateway_path("backup", &label_set).unwrap(),
+        "/metrics/job/backup/host/amazon.com/region/us-east-1",
+    );
+
+    assert_eq!(
+        r#"host="a",host="b""#.parse::<LabelSet>().unwrap_err().to_string(),
+        "duplicate key (\"host\")",
+    );
+}
+
+#[test]
+fn display_errors() {
+    use std::fmt::Write;
+
+    #[derive(Serialize)]
+    struct Labels {
+        #[serde(rename = "the host")]
+        host: &'static str,
+    }
+
+    let mut s = String::new();
+
+    assert!(write!(
+        s,
+        "{}",
+        display(&Labels {
+            host: "unionize.org"
+        })
+    )
+    .is_err());
+    assert_eq!(
+        display(&BTreeMap::from([("host", "\"quoted\"")])).to_string(),
+        r#"host="\"quoted\"""#,
+    );
+}
+
+#[test]
+fn fmt_output() {
+    #[derive(Serialize)]
+    struct Labels {
+        host: &'static str,
+        #[serde(rename = "the port")]
+        port: u16,
+    }
+
+    let mut s = "up{".to_owned();
+
+    to_output(
+        FmtOutput(&mut s),
+        &BTreeMap::from([("host", "unionize.org")]),
+    )
+    .unwrap();
+    assert_eq!(s, r#"up{host="unionize.org""#);
+
+    assert_eq!(
+        to_output(
+            FmtOutput(&mut s),
+            &Labels {
+                host: "unionize.org",
+                port: 80,
+            },
+        )
+        .unwrap_err()
+        .to_string(),
+        "invalid key (\"the port\")",
+    );
+}